@@ -1,4 +1,4 @@
-pub use self::anchor::Anchor;
+pub use self::anchor::{Anchor, Pool};
 pub use self::common::Block;
 pub use self::free::FreeBlock;
 pub use self::offset::Offset;
@@ -7,5 +7,7 @@ pub use self::used::UsedBlock;
 mod anchor;
 mod common;
 mod free;
+#[cfg(feature = "hardening")]
+mod hardening;
 mod offset;
 mod used;
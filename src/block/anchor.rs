@@ -1,64 +1,272 @@
 #![allow(unstable_name_collisions)]
 
 use core::marker::PhantomData;
-use core::mem::MaybeUninit;
+use core::mem::{self, MaybeUninit};
 use core::ptr::NonNull;
 
+#[allow(unused_imports)] // inherent methods on newer toolchains
 use sptr::Strict;
 
 use super::common::Header;
 use super::{Block, FreeBlock, Offset, UsedBlock};
-use crate::consts;
+use crate::{consts, FreeError, Size};
 
+/// A chunk of memory managed by the allocator
+///
+/// All the pools share a single offset space: each pool covers the range of (uncompressed)
+/// offsets that starts where the range of the previously added pool ends
 #[derive(Clone, Copy, Debug)]
-pub struct Anchor<'a> {
+pub struct Pool<'a> {
     ptr: NonNull<u32>,
+    // uncompressed offset of the first byte of the pool
+    base: usize,
+    // size of the pool in bytes
+    len: usize,
     _lifetime: PhantomData<&'a mut [MaybeUninit<u32>]>,
 }
 
+impl<'a> Pool<'a> {
+    pub const EMPTY: Self = Self {
+        ptr: NonNull::dangling(),
+        base: 0,
+        len: 0,
+        _lifetime: PhantomData,
+    };
+
+    /// # Safety
+    /// - `len` must not exceed the size of `memory` in bytes
+    pub unsafe fn new(memory: &'a mut [MaybeUninit<u32>], base: usize, len: usize) -> Self {
+        Self {
+            ptr: NonNull::new_unchecked(memory.as_mut_ptr()).cast(),
+            base,
+            len,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns the uncompressed offset right after the end of this pool
+    pub fn end(&self) -> usize {
+        self.base.wrapping_add(self.len)
+    }
+
+    /// Returns the number of bytes skipped at the start of a pool whose range of offsets starts at
+    /// `base`
+    pub fn skipped_size(base: usize) -> usize {
+        // skip the first `BLOCK_ALIGN` bytes of the offset space to ensure `Offset` is a non-zero
+        // value
+        if base == 0 {
+            consts::BLOCK_ALIGN.into()
+        } else {
+            0
+        }
+    }
+
+    /// Returns the offset of the first block in this pool
+    pub fn first_block(&self) -> Offset {
+        unsafe { Offset::compress(self.base.wrapping_add(Self::skipped_size(self.base))) }
+    }
+
+    /// Returns `true` if the byte at `addr` belongs to this pool
+    pub fn contains(&self, addr: usize) -> bool {
+        addr.wrapping_sub(self.ptr.as_ptr().addr()) < self.len
+    }
+
+    fn contains_offset(&self, offset: usize) -> bool {
+        offset.wrapping_sub(self.base) < self.len
+    }
+}
+
+/// Resolves offsets into pointers, and vice versa, using the pools managed by the allocator
+#[derive(Clone, Copy, Debug)]
+pub struct Anchor<'a> {
+    // NOTE a pointer rather than a reference so the anchor can be used while the allocator is
+    // mutably borrowed. The pools are only ever modified when a new pool is added.
+    pools: NonNull<[Pool<'a>]>,
+    #[cfg(feature = "safe-linking")]
+    secret: u32,
+}
+
 impl<'a> Anchor<'a> {
-    pub fn new(memory: &'a mut [MaybeUninit<u32>]) -> Self {
-        unsafe {
-            Anchor {
-                ptr: NonNull::new_unchecked(memory.as_mut_ptr()).cast(),
-                _lifetime: PhantomData,
-            }
+    /// # Safety
+    /// - `pools` must outlive the anchor and must not be modified while the anchor is in use
+    pub unsafe fn new(pools: &[Pool<'a>]) -> Self {
+        Self {
+            pools: NonNull::from(pools),
+            #[cfg(feature = "safe-linking")]
+            secret: 0,
         }
     }
 
+    /// Sets the secret that the blocks created by this anchor use to mask their free list links
+    /// and to key their header checksums
+    #[cfg(feature = "safe-linking")]
+    pub fn with_secret(self, secret: u32) -> Self {
+        Self { secret, ..self }
+    }
+
+    #[cfg(feature = "header-checksum")]
+    pub fn secret(&self) -> u32 {
+        self.secret
+    }
+
+    pub fn pools(&self) -> &[Pool<'a>] {
+        unsafe { self.pools.as_ref() }
+    }
+
+    /// Returns `true` if the `len` bytes at `offset` lie within a single pool
+    pub fn covers(&self, offset: Offset, len: usize) -> bool {
+        let start = offset.uncompress();
+        self.pools()
+            .iter()
+            .any(|pool| pool.contains_offset(start) && len <= pool.end().wrapping_sub(start))
+    }
+
     /// # Safety
     /// - caller must perform bounds checking
     pub unsafe fn create_free_block(
         &self,
         at: Offset,
-        usable_size: u16,
+        usable_size: Size,
         is_last_phys_block: bool,
         prev_phys_block: Option<Offset>,
     ) -> FreeBlock<'a> {
         let ptr = self.resolve_offset(at).cast();
-        FreeBlock::new(ptr, usable_size, is_last_phys_block, prev_phys_block)
+        let block = self.keyed(FreeBlock::new(
+            ptr,
+            usable_size,
+            is_last_phys_block,
+            prev_phys_block,
+        ));
+
+        #[cfg(feature = "safe-linking")]
+        {
+            block.clear_next_free();
+            block.clear_prev_free();
+        }
+        #[cfg(feature = "header-checksum")]
+        block.seal();
+
+        block
     }
 
     /// # Safety
     /// - caller must perform bounds checking
     pub unsafe fn get_free_block(&self, at: Offset) -> FreeBlock<'a> {
-        FreeBlock::from_ptr(self.resolve_offset(at).cast())
+        self.keyed(FreeBlock::from_ptr(self.resolve_offset(at).cast()))
+    }
+
+    /// Hands the secret of this anchor to `block`
+    fn keyed(&self, block: FreeBlock<'a>) -> FreeBlock<'a> {
+        #[cfg(feature = "safe-linking")]
+        let block = block.with_secret(self.secret);
+        block
+    }
+
+    /// # Safety
+    /// - caller must perform bounds checking
+    #[cfg(feature = "hardening")]
+    pub unsafe fn used_block_at(&self, at: Offset) -> UsedBlock<'a> {
+        UsedBlock::from_ptr(self.resolve_offset(at).cast())
     }
 
-    pub unsafe fn get_used_block(&self, body_ptr: NonNull<u32>) -> UsedBlock<'a> {
+    /// Returns `None` if `body_ptr` is not covered by any pool
+    pub unsafe fn get_used_block(&self, body_ptr: NonNull<u32>) -> Option<UsedBlock<'a>> {
+        let addr = body_ptr.as_ptr().addr();
+        let pool = self.pools().iter().find(|pool| pool.contains(addr))?;
+
         // cannot go through offset because `body_ptr` can be outside the `Offset` range
-        UsedBlock::from_ptr(
-            NonNull::new_unchecked(self.ptr.as_ptr().with_addr(
-                (body_ptr.as_ptr() as usize).wrapping_sub(UsedBlock::HEADER_SIZE.into()),
-            ))
+        Some(UsedBlock::from_ptr(
+            NonNull::new_unchecked(
+                pool.ptr
+                    .as_ptr()
+                    .with_addr(addr.wrapping_sub(UsedBlock::HEADER_SIZE.into())),
+            )
             .cast(),
-        )
+        ))
+    }
+
+    /// Like `get_used_block` but checks that `body_ptr` points at the body of a used block whose
+    /// header agrees with its physical neighbors
+    ///
+    /// All the headers that are read lie within the pool that contains `body_ptr`
+    pub fn find_used_block(&self, body_ptr: NonNull<u32>) -> Result<UsedBlock<'a>, FreeError> {
+        let addr = body_ptr.as_ptr().addr();
+        let pool = self
+            .pools()
+            .iter()
+            .find(|pool| pool.contains(addr))
+            .ok_or(FreeError::ForeignPointer)?;
+
+        if addr & (usize::from(consts::BLOCK_ALIGN) - 1) != 0 {
+            return Err(FreeError::Misaligned);
+        }
+
+        let header_size = usize::from(UsedBlock::HEADER_SIZE);
+        let first_block = pool.first_block();
+        let body = pool
+            .base
+            .wrapping_add(addr.wrapping_sub(pool.ptr.as_ptr().addr()));
+        // the body of the first block starts right after its header
+        if body < first_block.uncompress().wrapping_add(header_size) {
+            return Err(FreeError::NotABlock);
+        }
+        let start = body.wrapping_sub(header_size);
+
+        // `start` is within the pool, and so is the header, which ends at `body_ptr`
+        let offset = unsafe { Offset::compress(start) };
+        let block = unsafe { self.block_at(offset) };
+        if block.is_free() {
+            return Err(FreeError::DoubleFree);
+        }
+
+        let end = start.wrapping_add(block.total_size());
+        if block.usable_size() & (Size::from(consts::BLOCK_ALIGN) - 1) != 0 || end > pool.end() {
+            return Err(FreeError::NotABlock);
+        }
+
+        let prev_agrees = match block.raw_prev_phys_block() {
+            None => offset == first_block,
+            Some(prev) => {
+                let prev_start = prev.uncompress();
+                // `prev_start` lies between the first block and `start` so its header is within
+                // the pool
+                prev_start >= first_block.uncompress()
+                    && prev_start < start
+                    && prev_start.wrapping_add(unsafe { self.block_at(prev) }.total_size()) == start
+            }
+        };
+
+        let has_room = pool.end().wrapping_sub(end) >= usize::from(FreeBlock::HEADER_SIZE);
+        let next_agrees = if block.is_last_phys_block() {
+            !has_room
+        } else {
+            // the pool has room for the next header
+            has_room
+                && unsafe { self.block_at(Offset::compress(end)) }.raw_prev_phys_block()
+                    == Some(offset)
+        };
+
+        if !prev_agrees || !next_agrees {
+            return Err(FreeError::NotABlock);
+        }
+
+        let block = unsafe { UsedBlock::from_ptr(block.header_ptr().cast()) };
+
+        #[cfg(feature = "hardening")]
+        if unsafe { block.is_quarantined(*self) } {
+            return Err(FreeError::DoubleFree);
+        }
+
+        Ok(block)
     }
 
     /// # Safety
     /// - caller must perform bounds checking
     pub unsafe fn block_at(&self, at: Offset) -> Block<'a> {
-        Block::from_ptr(self.resolve_offset(at).cast())
+        let block = Block::from_ptr(self.resolve_offset(at).cast(), at);
+        #[cfg(feature = "header-checksum")]
+        let block = block.with_secret(self.secret);
+        block
     }
 
     /// # Safety
@@ -69,7 +277,7 @@ impl<'a> Anchor<'a> {
             return None;
         }
 
-        let distance = usize::from(block.usable_size()) + usize::from(UsedBlock::HEADER_SIZE);
+        let distance = block.usable_size() as usize + usize::from(UsedBlock::HEADER_SIZE);
         let block = self.compute_offset(block.header_ptr().cast());
         let next = block.add(distance);
         Some(self.block_at(next))
@@ -88,30 +296,57 @@ impl<'a> Anchor<'a> {
     }
 
     /// # Safety
-    /// - `ptr` must lie ahead of the anchor
-    /// - `ptr` must lie within `1 << 18` bytes of the anchor
+    /// - `ptr` must be covered by one of the pools
     unsafe fn compute_offset(&self, ptr: NonNull<u32>) -> Offset {
-        let anchor = self.ptr.as_ptr().addr();
-        let offset = ptr.as_ptr().addr().wrapping_sub(anchor);
+        let addr = ptr.as_ptr().addr();
+        let pool = self.find_pool(|pool| pool.contains(addr));
+        let offset = pool
+            .base
+            .wrapping_add(addr.wrapping_sub(pool.ptr.as_ptr().addr()));
         unsafe { Offset::compress(offset) }
     }
 
     /// # Safety
     /// - `offset` must have been produced using `compute_offset` on this anchor
     unsafe fn resolve_offset(&self, offset: Offset) -> NonNull<u32> {
-        NonNull::new_unchecked(self.ptr.as_ptr().add(offset.get()))
+        let offset = offset.uncompress();
+        let pool = self.find_pool(|pool| pool.contains_offset(offset));
+        NonNull::new_unchecked(
+            pool.ptr
+                .as_ptr()
+                .cast::<u8>()
+                .add(offset.wrapping_sub(pool.base))
+                .cast(),
+        )
+    }
+
+    /// # Safety
+    /// - `addr` must be covered by one of the pools
+    pub(super) unsafe fn with_addr(&self, addr: usize) -> NonNull<u32> {
+        let pool = self.find_pool(|pool| pool.contains(addr));
+        NonNull::new_unchecked(pool.ptr.as_ptr().with_addr(addr))
     }
 
-    unsafe fn with_addr(&self, addr: usize) -> NonNull<u32> {
-        NonNull::new_unchecked(self.ptr.as_ptr().with_addr(addr))
+    /// # Safety
+    /// - one of the pools must satisfy the `predicate`
+    unsafe fn find_pool(&self, predicate: impl FnMut(&&Pool<'a>) -> bool) -> &Pool<'a> {
+        let pool = self.pools().iter().find(predicate);
+
+        #[cfg(any(fuzzing, test))]
+        debug_assert!(pool.is_some());
+
+        pool.unwrap_unchecked()
     }
 }
 
 impl<'a> FreeBlock<'a> {
     pub unsafe fn into_used(self, anchor: Anchor<'a>) -> &'a mut [MaybeUninit<u32>] {
-        let size = usize::from(self.usable_size());
+        let size = self.usable_size() as usize;
 
-        self.header_ptr().cast::<Header>().as_ref().set_free(false);
+        let header = self.header_ptr().cast::<Header>().as_ref();
+        header.set_free(false);
+        #[cfg(feature = "header-checksum")]
+        header.seal(anchor.secret);
 
         let data_ptr = anchor
             .with_addr(
@@ -119,28 +354,49 @@ impl<'a> FreeBlock<'a> {
                     .wrapping_add(UsedBlock::HEADER_SIZE.into()),
             )
             .as_ptr();
+        let len = size / mem::size_of::<u32>();
 
-        core::slice::from_raw_parts_mut(data_ptr.cast(), size >> consts::BLOCK_ALIGN_LOG2)
+        #[cfg(feature = "hardening")]
+        let len = Self::write_canary(data_ptr, len);
+
+        core::slice::from_raw_parts_mut(data_ptr.cast(), len)
     }
 }
 
 impl<'a> UsedBlock<'a> {
     pub unsafe fn into_free(self, anchor: Anchor<'a>) -> FreeBlock<'a> {
-        self.header_ptr().cast::<Header>().as_ref().set_free(true);
-
-        let free = FreeBlock::from_ptr(anchor.with_addr(self.header_addr()).cast());
+        let header = self.header_ptr().cast::<Header>().as_ref();
+        header.set_free(true);
+        #[cfg(feature = "header-checksum")]
+        header.seal(anchor.secret);
+
+        let free = anchor.keyed(FreeBlock::from_ptr(
+            anchor.with_addr(self.header_addr()).cast(),
+        ));
         free.clear_next_free();
         free.clear_prev_free();
         free
     }
+
+    /// Grants access to the block-level operations of `FreeBlock` (`split`, `merge`, etc.)
+    /// without marking the block as free
+    ///
+    /// # Safety
+    /// - caller must not access the free list links of the returned block as they overlap with the
+    ///   body of the used block
+    pub unsafe fn as_free_unchecked(&self, anchor: Anchor<'a>) -> FreeBlock<'a> {
+        anchor.keyed(FreeBlock::from_ptr(
+            anchor.with_addr(self.header_addr()).cast(),
+        ))
+    }
 }
 
 impl<'a> Block<'a> {
     pub(crate) unsafe fn try_into_free(self, anchor: Anchor<'a>) -> Option<FreeBlock<'a>> {
         if self.is_free() {
-            Some(FreeBlock::from_ptr(
+            Some(anchor.keyed(FreeBlock::from_ptr(
                 anchor.with_addr(self.header_addr()).cast(),
-            ))
+            )))
         } else {
             None
         }
@@ -150,7 +406,7 @@ impl<'a> Block<'a> {
 trait NextPhysBlock {
     fn header_ptr(&self) -> NonNull<Header>;
     fn is_last_phys_block(&self) -> bool;
-    fn usable_size(&self) -> u16;
+    fn usable_size(&self) -> Size;
 }
 
 trait OffsetOf {
@@ -163,6 +419,12 @@ impl OffsetOf for FreeBlock<'_> {
     }
 }
 
+impl OffsetOf for UsedBlock<'_> {
+    fn header_ptr(&self) -> NonNull<Header> {
+        Self::header_ptr(self).cast()
+    }
+}
+
 impl OffsetOf for Block<'_> {
     fn header_ptr(&self) -> NonNull<Header> {
         Self::header_ptr(self).cast()
@@ -174,7 +436,7 @@ impl NextPhysBlock for Block<'_> {
         Self::is_last_phys_block(self)
     }
 
-    fn usable_size(&self) -> u16 {
+    fn usable_size(&self) -> Size {
         Self::usable_size(self)
     }
 
@@ -188,7 +450,7 @@ impl NextPhysBlock for FreeBlock<'_> {
         Self::is_last_phys_block(self)
     }
 
-    fn usable_size(&self) -> u16 {
+    fn usable_size(&self) -> Size {
         Self::usable_size(self)
     }
 
@@ -197,16 +459,22 @@ impl NextPhysBlock for FreeBlock<'_> {
     }
 }
 
+// with wide offsets, the offset space is too large to allocate
+#[cfg(not(feature = "wide-offsets"))]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::Memory;
 
     #[test]
     fn furthest_free_block_into_used() {
-        let mut memory = [MaybeUninit::uninit(); 65 * 1024];
-        let anchor = Anchor::new(&mut memory[..]);
+        let mut memory = Memory::new();
+        let memory = memory.bytes();
+        let len = memory.len() * 4;
+        let pools = unsafe { [Pool::new(memory, 0, len)] };
+        let anchor = unsafe { Anchor::new(&pools) };
         unsafe {
-            let free = anchor.create_free_block(Offset::max(), 4, true, None);
+            let free = anchor.create_free_block(Offset::max(), consts::MIN_USABLE_SIZE, true, None);
             let used = free.into_used(anchor);
             used.iter_mut().for_each(|mu| {
                 mu.write(!0);
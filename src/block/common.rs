@@ -2,9 +2,10 @@ use core::cell::Cell;
 use core::fmt;
 use core::ptr::NonNull;
 
-use super::Offset;
+use super::{Offset, UsedBlock};
 #[cfg(any(fuzzing, test))]
 use crate::consts;
+use crate::Size;
 #[allow(unused_imports)] // used by API docs
 use crate::Tlsf;
 
@@ -14,11 +15,14 @@ use crate::Tlsf;
 /// memory returned by [`Tlsf::memalign`]
 pub struct Block<'a> {
     header: &'a Header,
+    offset: Offset,
+    #[cfg(feature = "header-checksum")]
+    secret: u32,
 }
 
 impl Block<'_> {
     /// Returns the usable size of the memory block in bytes
-    pub fn usable_size(&self) -> u16 {
+    pub fn usable_size(&self) -> Size {
         self.header.usable_size()
     }
 
@@ -38,28 +42,74 @@ impl Block<'_> {
         !self.header.is_free()
     }
 
-    pub(super) unsafe fn from_ptr(ptr: NonNull<Header>) -> Self {
+    /// Returns the size of the memory block in bytes, including its header
+    pub fn total_size(&self) -> usize {
+        self.usable_size() as usize + usize::from(UsedBlock::HEADER_SIZE)
+    }
+
+    /// Returns the address of the body of the memory block
+    ///
+    /// For a used block, this is the address of the memory returned by the allocator
+    pub fn body_addr(&self) -> usize {
+        self.header_addr() + usize::from(UsedBlock::HEADER_SIZE)
+    }
+
+    /// Returns the compressed offset of the memory block
+    ///
+    /// All the pools share a single offset space; the compressed offset is the position of the
+    /// block's header in that space divided by the block granularity (4 bytes by default)
+    pub fn offset(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// Returns the compressed offset (see [`Block::offset`]) of the block that physically
+    /// precedes this one, or `None` if this is the first block of its pool
+    pub fn prev_phys_block(&self) -> Option<usize> {
+        self.header.get_prev_phys_block().map(|offset| offset.get())
+    }
+
+    /// Returns `true` if this is the last memory block of its pool
+    pub fn is_last_phys_block(&self) -> bool {
+        self.header.is_last_phys_block()
+    }
+
+    pub(crate) fn raw_offset(&self) -> Offset {
+        self.offset
+    }
+
+    pub(super) fn raw_prev_phys_block(&self) -> Option<Offset> {
+        self.header.get_prev_phys_block()
+    }
+
+    pub(super) unsafe fn from_ptr(ptr: NonNull<Header>, offset: Offset) -> Self {
         Self {
             header: ptr.as_ref(),
+            offset,
+            #[cfg(feature = "header-checksum")]
+            secret: 0,
         }
     }
 
-    pub(super) fn header_addr(&self) -> usize {
-        self.header as *const Header as usize
+    /// Sets the secret the header checksum is keyed with
+    #[cfg(feature = "header-checksum")]
+    pub(super) fn with_secret(self, secret: u32) -> Self {
+        Self { secret, ..self }
     }
 
-    #[cfg(test)]
-    pub(crate) fn total_size(&self) -> usize {
-        use super::UsedBlock;
-
-        usize::from(self.usable_size()) + usize::from(UsedBlock::HEADER_SIZE)
+    /// Recomputes the checksum of the header
+    #[cfg(feature = "header-checksum")]
+    pub(crate) fn seal(&self) {
+        self.header.seal(self.secret);
     }
-    pub(super) fn is_last_phys_block(&self) -> bool {
-        self.header.is_last_phys_block()
+
+    pub(super) fn header_addr(&self) -> usize {
+        self.header as *const Header as usize
     }
 
     pub(crate) fn set_prev_phys_block(&self, offset: Offset) {
         self.header.set_prev_phys_block(offset);
+        #[cfg(feature = "header-checksum")]
+        self.seal();
     }
 
     pub(super) fn header_ptr(&self) -> NonNull<Header> {
@@ -72,29 +122,37 @@ impl fmt::Debug for Block<'_> {
         f.debug_struct("Block")
             .field("is_free", &self.is_free())
             .field("usable_size", &self.usable_size())
+            .field("offset", &self.offset())
             .finish()
     }
 }
+// the size of the header of a used block must be a multiple of `BLOCK_ALIGN` so that the body of
+// the block is `BLOCK_ALIGN`-byte aligned
 #[repr(C)]
-#[repr(align(4))]
+#[cfg_attr(not(any(feature = "align-8", feature = "align-16")), repr(align(4)))]
+#[cfg_attr(all(feature = "align-8", not(feature = "align-16")), repr(align(8)))]
+#[cfg_attr(feature = "align-16", repr(align(16)))]
 pub(super) struct Header {
-    size_free_last: Cell<u16>,
+    size_free_last: Cell<Size>,
     prev_phys_block: Cell<Option<Offset>>,
+    // covers the other fields; see `Header::seal`
+    #[cfg(feature = "header-checksum")]
+    checksum: Cell<u32>,
 }
 
 impl Header {
-    const FREE_BIT: u16 = 1 << 0;
-    const LAST_PHYS_BLOCK_BIT: u16 = 1 << 1;
-    const SIZE_MASK: u16 = !(Self::FREE_BIT | Self::LAST_PHYS_BLOCK_BIT);
+    const FREE_BIT: Size = 1 << 0;
+    const LAST_PHYS_BLOCK_BIT: Size = 1 << 1;
+    const SIZE_MASK: Size = !(Self::FREE_BIT | Self::LAST_PHYS_BLOCK_BIT);
 
     pub fn new(
-        usable_size: u16,
+        usable_size: Size,
         is_free: bool,
         is_last_phys_block: bool,
         prev_phys_block: Option<Offset>,
     ) -> Self {
         #[cfg(any(fuzzing, test))]
-        debug_assert_eq!(0, usable_size % u16::from(consts::BLOCK_ALIGN));
+        debug_assert_eq!(0, usable_size % Size::from(consts::BLOCK_ALIGN));
 
         let mut size_free_last = usable_size;
         if is_free {
@@ -107,10 +165,13 @@ impl Header {
         Self {
             size_free_last: Cell::new(size_free_last),
             prev_phys_block: Cell::new(prev_phys_block),
+            // sealed once the header is in place
+            #[cfg(feature = "header-checksum")]
+            checksum: Cell::new(0),
         }
     }
 
-    pub fn usable_size(&self) -> u16 {
+    pub fn usable_size(&self) -> Size {
         self.size_free_last.get() & Self::SIZE_MASK
     }
 
@@ -128,9 +189,9 @@ impl Header {
         });
     }
 
-    pub unsafe fn set_usable_size(&self, new_usable_size: u16) {
+    pub unsafe fn set_usable_size(&self, new_usable_size: Size) {
         #[cfg(any(fuzzing, test))]
-        debug_assert_eq!(0, new_usable_size % 4);
+        debug_assert_eq!(0, new_usable_size % Size::from(consts::BLOCK_ALIGN));
 
         update(&self.size_free_last, |old| {
             let free_last = old & !Self::SIZE_MASK;
@@ -157,6 +218,39 @@ impl Header {
     pub fn set_prev_phys_block(&self, prev_phys_block: Offset) {
         self.prev_phys_block.set(Some(prev_phys_block));
     }
+
+    /// Stores the checksum of the header; must be called after every change to the header
+    #[cfg(feature = "header-checksum")]
+    pub fn seal(&self, secret: u32) {
+        self.checksum.set(self.compute_checksum(secret));
+    }
+
+    /// Returns `true` if the header has not changed since it was last sealed with `secret`
+    #[cfg(feature = "header-checksum")]
+    pub fn is_sealed(&self, secret: u32) -> bool {
+        self.checksum.get() == self.compute_checksum(secret)
+    }
+
+    // the address of the header is part of the key so a header copied to another place does not
+    // pass the check
+    #[cfg(feature = "header-checksum")]
+    fn compute_checksum(&self, secret: u32) -> u32 {
+        let addr = self as *const Header as usize;
+        let prev_phys_block = self.prev_phys_block.get().map_or(0, |offset| offset.get());
+        [self.size_free_last.get() as usize, prev_phys_block]
+            .into_iter()
+            .fold(secret ^ addr as u32, |hash, word| mix(hash ^ word as u32))
+    }
+}
+
+/// Finalizer of the 32-bit MurmurHash3
+#[cfg(feature = "header-checksum")]
+fn mix(mut hash: u32) -> u32 {
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ hash >> 16
 }
 
 fn update<T>(cell: &Cell<T>, f: impl FnOnce(T) -> T)
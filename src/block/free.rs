@@ -3,13 +3,18 @@ use core::ptr::NonNull;
 use core::{fmt, mem};
 
 use super::common::Header;
+#[cfg(feature = "safe-linking")]
+use super::offset::MaskedOffset;
 use super::used::UsedHeader;
 use super::{Offset, UsedBlock};
+use crate::Size;
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(Clone))]
 pub struct FreeBlock<'a> {
     header: &'a FreeHeader,
+    #[cfg(feature = "safe-linking")]
+    secret: u32,
 }
 
 impl<'a> FreeBlock<'a> {
@@ -17,13 +22,13 @@ impl<'a> FreeBlock<'a> {
 
     pub(super) unsafe fn new(
         ptr: NonNull<FreeHeader>,
-        usable_size: u16,
+        usable_size: Size,
         is_last_phys_block: bool,
         prev_phys_block: Option<Offset>,
     ) -> Self {
         #[cfg(any(fuzzing, test))]
         debug_assert!(
-            usize::from(usable_size) + usize::from(UsedBlock::HEADER_SIZE)
+            usable_size as usize + usize::from(UsedBlock::HEADER_SIZE)
                 >= usize::from(FreeBlock::HEADER_SIZE)
         );
 
@@ -35,17 +40,29 @@ impl<'a> FreeBlock<'a> {
                 prev_phys_block,
             ))
         }
-        Self {
-            header: ptr.as_ref(),
-        }
+        Self::from_ptr(ptr)
     }
 
     pub(super) unsafe fn from_ptr(ptr: NonNull<FreeHeader>) -> Self {
         Self {
             header: ptr.as_ref(),
+            #[cfg(feature = "safe-linking")]
+            secret: 0,
         }
     }
 
+    /// Sets the secret the free list links are masked with
+    #[cfg(feature = "safe-linking")]
+    pub(super) fn with_secret(self, secret: u32) -> Self {
+        Self { secret, ..self }
+    }
+
+    /// Updates the checksum of the header after a change
+    #[cfg(feature = "header-checksum")]
+    pub(super) fn seal(&self) {
+        self.header.common.seal(self.secret);
+    }
+
     pub(super) fn header_ptr(&self) -> NonNull<FreeHeader> {
         NonNull::from(self.header)
     }
@@ -54,12 +71,22 @@ impl<'a> FreeBlock<'a> {
         self.header as *const FreeHeader as usize
     }
 
-    pub unsafe fn resize(&self, new_usable_size: u16) {
+    pub unsafe fn resize(&self, new_usable_size: Size) {
         self.header.common.set_usable_size(new_usable_size);
+        #[cfg(feature = "header-checksum")]
+        self.seal();
+    }
+
+    /// Zeroes the header of the block once it has been merged into another block
+    ///
+    /// # Safety
+    /// - the block must no longer be part of the heap
+    pub unsafe fn clear(self) {
+        self.header_ptr().as_ptr().write_bytes(0, 1);
     }
 
     pub fn total_size(&self) -> usize {
-        usize::from(self.usable_size()) + usize::from(UsedBlock::HEADER_SIZE)
+        self.usable_size() as usize + usize::from(UsedBlock::HEADER_SIZE)
     }
 
     pub fn body_ptr(&self) -> NonNull<u32> {
@@ -69,7 +96,7 @@ impl<'a> FreeBlock<'a> {
     }
 
     // header manipulation
-    pub fn usable_size(&self) -> u16 {
+    pub fn usable_size(&self) -> Size {
         self.header.common.usable_size()
     }
 
@@ -78,11 +105,15 @@ impl<'a> FreeBlock<'a> {
     }
 
     pub fn set_last_phys_block(&self) {
-        self.header.common.set_last_phys_block()
+        self.header.common.set_last_phys_block();
+        #[cfg(feature = "header-checksum")]
+        self.seal();
     }
 
     pub fn clear_last_phys_block(&self) {
-        self.header.common.clear_last_phys_block()
+        self.header.common.clear_last_phys_block();
+        #[cfg(feature = "header-checksum")]
+        self.seal();
     }
 
     pub fn get_prev_phys_block(&self) -> Option<Offset> {
@@ -91,44 +122,78 @@ impl<'a> FreeBlock<'a> {
 
     // FreeBlock-specific header manipulation
     pub fn clear_next_free(&self) {
-        self.header.next_free.set(None);
+        self.store(&self.header.next_free, None);
     }
 
     pub fn get_next_free(&self) -> Option<Offset> {
-        self.header.next_free.get()
+        self.load(&self.header.next_free)
     }
 
     pub fn set_next_free(&self, offset: Offset) {
-        self.header.next_free.set(Some(offset));
+        self.store(&self.header.next_free, Some(offset));
     }
 
     pub fn clear_prev_free(&self) {
-        self.header.prev_free.set(None);
+        self.store(&self.header.prev_free, None);
     }
 
     pub fn set_prev_free(&self, offset: Offset) {
-        self.header.prev_free.set(Some(offset));
+        self.store(&self.header.prev_free, Some(offset));
     }
 
     pub fn get_prev_free(&self) -> Option<Offset> {
-        self.header.prev_free.get()
+        self.load(&self.header.prev_free)
+    }
+
+    #[cfg(not(feature = "safe-linking"))]
+    fn load(&self, link: &Cell<Link>) -> Option<Offset> {
+        link.get()
+    }
+
+    #[cfg(not(feature = "safe-linking"))]
+    fn store(&self, link: &Cell<Link>, offset: Option<Offset>) {
+        link.set(offset);
+    }
+
+    #[cfg(feature = "safe-linking")]
+    fn load(&self, link: &Cell<Link>) -> Option<Offset> {
+        link.get().unmask(self.key(link))
+    }
+
+    #[cfg(feature = "safe-linking")]
+    fn store(&self, link: &Cell<Link>, offset: Option<Offset>) {
+        link.set(MaskedOffset::mask(offset, self.key(link)));
+    }
+
+    // the address of the link is part of the key so a link copied to another place unmasks to
+    // a different offset
+    #[cfg(feature = "safe-linking")]
+    fn key(&self, link: &Cell<Link>) -> usize {
+        self.secret as usize ^ link as *const Cell<Link> as usize
     }
 }
 
+#[cfg(not(feature = "safe-linking"))]
+type Link = Option<Offset>;
+#[cfg(feature = "safe-linking")]
+type Link = MaskedOffset;
+
 #[repr(C)]
 #[repr(align(4))]
 pub struct FreeHeader {
     common: Header,
-    next_free: Cell<Option<Offset>>,
-    prev_free: Cell<Option<Offset>>,
+    next_free: Cell<Link>,
+    prev_free: Cell<Link>,
 }
 
 impl FreeHeader {
-    fn new(usable_size: u16, is_last_phys_block: bool, prev_phys_block: Option<Offset>) -> Self {
+    fn new(usable_size: Size, is_last_phys_block: bool, prev_phys_block: Option<Offset>) -> Self {
         Self {
             common: Header::new(usable_size, true, is_last_phys_block, prev_phys_block),
-            next_free: Cell::new(None),
-            prev_free: Cell::new(None),
+            // with `safe-linking`, `Anchor::create_free_block` masks the links once the block has
+            // its secret
+            next_free: Cell::new(Link::default()),
+            prev_free: Cell::new(Link::default()),
         }
     }
 }
@@ -145,6 +210,14 @@ impl fmt::Debug for FreeHeader {
     }
 }
 
+// the layout features make the header larger
+#[cfg(not(any(
+    feature = "wide-offsets",
+    feature = "wide-sizes",
+    feature = "align-8",
+    feature = "align-16",
+    feature = "header-checksum"
+)))]
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,121 @@
+use core::mem;
+
+use super::{Anchor, FreeBlock, UsedBlock};
+use crate::consts;
+
+impl<'a> FreeBlock<'a> {
+    /// Writes the canary in the last of the `len` words of the body at `body` and returns the
+    /// number of words left for the allocation
+    ///
+    /// # Safety
+    /// - `body` must point to `len` writable words; `len` must not be zero
+    pub(super) unsafe fn write_canary(body: *mut u32, len: usize) -> usize {
+        let len = len.wrapping_sub(1);
+        body.add(len).write(consts::CANARY);
+        len
+    }
+
+    /// Fills the body of the block, past the free list links, with the poison pattern
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor`
+    pub unsafe fn poison(&self, anchor: Anchor<'a>) {
+        if let Some((ptr, len)) = self.poisoned_words(anchor) {
+            for i in 0..len {
+                ptr.add(i).write(consts::POISON);
+            }
+        }
+    }
+
+    /// Returns `true` if the body of the block still holds the poison pattern
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor` and must have been poisoned
+    pub unsafe fn is_poisoned(&self, anchor: Anchor<'a>) -> bool {
+        match self.poisoned_words(anchor) {
+            Some((ptr, len)) => (0..len).all(|i| ptr.add(i).read() == consts::POISON),
+            None => true,
+        }
+    }
+
+    /// Returns the words of the body that don't overlap with the free list links, if any
+    unsafe fn poisoned_words(&self, anchor: Anchor<'a>) -> Option<(*mut u32, usize)> {
+        let end = self.header_addr().wrapping_add(self.total_size());
+        body_words(anchor, self.header_addr(), end)
+    }
+}
+
+impl<'a> UsedBlock<'a> {
+    /// Returns `true` if the canary in the last word of the body is intact
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor`
+    pub unsafe fn has_canary(&self, anchor: Anchor<'a>) -> bool {
+        self.last_word(anchor).read() == consts::CANARY
+    }
+
+    /// Poisons the body of the block, past the quarantine link, and replaces the canary with the
+    /// quarantine marker
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor`
+    pub unsafe fn quarantine(&self, anchor: Anchor<'a>) {
+        if let Some((ptr, len)) = self.quarantined_words(anchor) {
+            for i in 0..len {
+                ptr.add(i).write(consts::POISON);
+            }
+        }
+        self.last_word(anchor).write(consts::QUARANTINED);
+    }
+
+    /// Returns `true` if the block is marked as quarantined
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor`
+    pub unsafe fn is_quarantined(&self, anchor: Anchor<'a>) -> bool {
+        self.last_word(anchor).read() == consts::QUARANTINED
+    }
+
+    /// Returns `true` if the body of the quarantined block still holds the poison pattern
+    ///
+    /// # Safety
+    /// - block must be covered by `anchor` and must have been quarantined
+    pub unsafe fn is_quarantine_poisoned(&self, anchor: Anchor<'a>) -> bool {
+        match self.quarantined_words(anchor) {
+            Some((ptr, len)) => (0..len).all(|i| ptr.add(i).read() == consts::POISON),
+            None => true,
+        }
+    }
+
+    /// Returns the words of the body between the quarantine link and the last word, if any
+    unsafe fn quarantined_words(&self, anchor: Anchor<'a>) -> Option<(*mut u32, usize)> {
+        let end = self.body_end().wrapping_sub(mem::size_of::<u32>());
+        body_words(anchor, self.header_addr(), end)
+    }
+
+    unsafe fn last_word(&self, anchor: Anchor<'a>) -> *mut u32 {
+        let addr = self.body_end().wrapping_sub(mem::size_of::<u32>());
+        anchor.with_addr(addr).as_ptr()
+    }
+
+    fn body_end(&self) -> usize {
+        self.header_addr()
+            .wrapping_add(UsedBlock::HEADER_SIZE.into())
+            .wrapping_add(self.usable_size() as usize)
+    }
+}
+
+/// Returns the words between the free list links of the block at `header_addr` and `end`, if any
+unsafe fn body_words(
+    anchor: Anchor<'_>,
+    header_addr: usize,
+    end: usize,
+) -> Option<(*mut u32, usize)> {
+    let start = header_addr.wrapping_add(FreeBlock::HEADER_SIZE.into());
+    let len = end.saturating_sub(start) / mem::size_of::<u32>();
+    if len == 0 {
+        return None;
+    }
+
+    Some((anchor.with_addr(start).as_ptr(), len))
+}
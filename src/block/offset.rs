@@ -1,30 +1,42 @@
-use core::num::NonZeroU16;
+#[cfg(not(feature = "wide-offsets"))]
+use core::num::NonZeroU16 as NonZeroRepr;
+#[cfg(feature = "wide-offsets")]
+use core::num::NonZeroU32 as NonZeroRepr;
 
 use crate::consts;
 
+// width of a compressed offset; selected with the `wide-offsets` feature
+#[cfg(not(feature = "wide-offsets"))]
+type Repr = u16;
+#[cfg(feature = "wide-offsets")]
+type Repr = u32;
+
 /// Compressed offset
-#[derive(Debug, Clone, Copy)]
-#[cfg_attr(test, derive(PartialOrd, PartialEq, Ord, Eq))]
-pub struct Offset(NonZeroU16);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(PartialOrd, Ord))]
+pub struct Offset(NonZeroRepr);
 
 impl Offset {
-    pub const MAX_UNCOMPRESSED_VALUE: usize = (u16::MAX as usize) << consts::BLOCK_ALIGN_LOG2;
+    // saturates on targets where `usize` can't hold the uncompressed value, e.g. 32-bit targets
+    // with wide offsets; the address space is the limit in that case
+    pub const MAX_UNCOMPRESSED_VALUE: usize =
+        (Repr::MAX as usize).saturating_mul(consts::BLOCK_ALIGN as usize);
 
     /// compresses the given `offset`
     ///
     /// # Safety
-    /// - caller must ensure `offset` fits into a `u16` value after compression
+    /// - caller must ensure `offset` fits into a `Repr` value after compression
     pub unsafe fn compress(offset: usize) -> Self {
         let compressed = offset >> consts::BLOCK_ALIGN_LOG2;
 
         #[cfg(any(fuzzing, test))]
-        debug_assert!(u16::try_from(compressed).is_ok());
+        debug_assert!(Repr::try_from(compressed).is_ok());
 
-        Self(NonZeroU16::new_unchecked(compressed as u16))
+        Self(NonZeroRepr::new_unchecked(compressed as Repr))
     }
 
     /// returns the compressed offset value
-    pub(super) fn get(&self) -> usize {
+    pub fn get(&self) -> usize {
         self.0.get() as usize
     }
 
@@ -34,12 +46,30 @@ impl Offset {
         Self::compress(self.uncompress().wrapping_add(uncompressed))
     }
 
-    #[cfg(test)]
+    // the offset space of `wide-offsets` is too large to allocate in the tests
+    #[cfg(all(test, not(feature = "wide-offsets")))]
     pub fn max() -> Self {
-        Self(NonZeroU16::new(u16::MAX).unwrap())
+        Self(NonZeroRepr::new(Repr::MAX).unwrap())
     }
 
-    pub(super) fn uncompress(&self) -> usize {
+    pub fn uncompress(&self) -> usize {
         self.get() << consts::BLOCK_ALIGN_LOG2
     }
 }
+
+/// A free list link as stored in a free block: a compressed offset, or `None`, XORed with a key
+#[cfg(feature = "safe-linking")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaskedOffset(Repr);
+
+#[cfg(feature = "safe-linking")]
+impl MaskedOffset {
+    /// Only the low bits of `key` that fit in a compressed offset are used
+    pub fn mask(offset: Option<Offset>, key: usize) -> Self {
+        Self(offset.map_or(0, |offset| offset.0.get()) ^ key as Repr)
+    }
+
+    pub fn unmask(self, key: usize) -> Option<Offset> {
+        NonZeroRepr::new(self.0 ^ key as Repr).map(Offset)
+    }
+}
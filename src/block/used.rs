@@ -2,6 +2,7 @@ use core::ptr::NonNull;
 use core::{fmt, mem};
 
 use super::common::Header;
+use crate::Size;
 
 #[derive(Debug)]
 pub struct UsedBlock<'a> {
@@ -24,6 +25,16 @@ impl<'a> UsedBlock<'a> {
     pub(super) fn header_addr(&self) -> usize {
         self.header as *const UsedHeader as usize
     }
+
+    pub fn usable_size(&self) -> Size {
+        self.header.common.usable_size()
+    }
+
+    /// Returns `true` if the checksum of the header matches its contents
+    #[cfg(feature = "header-checksum")]
+    pub fn is_sealed(&self, secret: u32) -> bool {
+        self.header.common.is_sealed(secret)
+    }
 }
 
 #[repr(C)]
@@ -42,6 +53,14 @@ impl fmt::Debug for UsedHeader {
     }
 }
 
+// the layout features make the header larger
+#[cfg(not(any(
+    feature = "wide-offsets",
+    feature = "wide-sizes",
+    feature = "align-8",
+    feature = "align-16",
+    feature = "header-checksum"
+)))]
 #[cfg(test)]
 mod tests {
     use super::*;
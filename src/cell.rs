@@ -0,0 +1,331 @@
+use core::cell::UnsafeCell;
+
+use crate::sl::{SecondLevel, Sl};
+use crate::Tlsf;
+
+/// A [`Tlsf`] allocator that can be shared, by reference, within a single thread
+///
+/// `&TlsfCell` implements the `Allocator` trait of the `allocator_api` nightly feature when the
+/// `nightly` feature is enabled, and the `Allocator` trait of the `allocator-api2` crate when the
+/// `allocator-api2` feature is enabled. Zero-sized allocations do not use memory from the
+/// allocator.
+///
+#[cfg_attr(feature = "nightly", doc = "```")]
+#[cfg_attr(not(feature = "nightly"), doc = "```ignore")]
+/// #![feature(allocator_api)]
+///
+/// use core::mem::MaybeUninit;
+///
+/// use tlsf::{Tlsf, TlsfCell};
+///
+/// let mut memory = [MaybeUninit::uninit(); 256];
+/// let mut tlsf = Tlsf::<2>::empty();
+/// tlsf.initialize(&mut memory);
+/// let tlsf = TlsfCell::new(tlsf);
+///
+/// let mut xs = Vec::new_in(&tlsf);
+/// xs.extend([1, 2, 3]);
+/// let boxed = Box::new_in(42, &tlsf);
+/// ```
+pub struct TlsfCell<'a, const FLL: usize, const POOLS: usize = 1, const SL: usize = 16>
+where
+    Sl<SL>: SecondLevel,
+{
+    inner: UnsafeCell<Tlsf<'a, FLL, POOLS, SL>>,
+}
+
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> TlsfCell<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Wraps the given allocator
+    pub const fn new(tlsf: Tlsf<'a, FLL, POOLS, SL>) -> Self {
+        Self {
+            inner: UnsafeCell::new(tlsf),
+        }
+    }
+
+    /// Returns a mutable reference to the inner allocator
+    pub fn get_mut(&mut self) -> &mut Tlsf<'a, FLL, POOLS, SL> {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps the inner allocator
+    pub fn into_inner(self) -> Tlsf<'a, FLL, POOLS, SL> {
+        self.inner.into_inner()
+    }
+
+    /// # Safety
+    /// - the returned reference must be dropped before `tlsf` is accessed again. `TlsfCell` is not
+    ///   `Sync` and the only user code that the allocator methods call into is the corruption hook
+    ///   of the `hardening` feature, which must not use the allocator, so this holds as long as the
+    ///   reference does not escape the method that called `tlsf`
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn tlsf(&self) -> &mut Tlsf<'a, FLL, POOLS, SL> {
+        &mut *self.inner.get()
+    }
+}
+
+mod allocator {
+    use core::alloc::Layout;
+    use core::mem;
+    use core::ptr::NonNull;
+
+    use super::TlsfCell;
+    use crate::sl::{SecondLevel, Sl};
+
+    // implements an `Allocator` trait, which has the same API in `core` and `allocator-api2`, for
+    // `&TlsfCell`
+    macro_rules! allocator {
+        ($allocator:path, $alloc_error:path) => {
+            unsafe impl<const FLL: usize, const POOLS: usize, const SL: usize> $allocator
+                for &TlsfCell<'_, FLL, POOLS, SL>
+            where
+                Sl<SL>: SecondLevel,
+            {
+                fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, $alloc_error> {
+                    self.allocate_bytes(layout).ok_or($alloc_error)
+                }
+
+                fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, $alloc_error> {
+                    self.allocate_zeroed_bytes(layout).ok_or($alloc_error)
+                }
+
+                unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                    self.deallocate_bytes(ptr, layout)
+                }
+
+                unsafe fn grow(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, $alloc_error> {
+                    self.resize_bytes(ptr, old_layout, new_layout)
+                        .ok_or($alloc_error)
+                }
+
+                unsafe fn grow_zeroed(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, $alloc_error> {
+                    self.grow_zeroed_bytes(ptr, old_layout, new_layout)
+                        .ok_or($alloc_error)
+                }
+
+                unsafe fn shrink(
+                    &self,
+                    ptr: NonNull<u8>,
+                    old_layout: Layout,
+                    new_layout: Layout,
+                ) -> Result<NonNull<[u8]>, $alloc_error> {
+                    self.resize_bytes(ptr, old_layout, new_layout)
+                        .ok_or($alloc_error)
+                }
+            }
+        };
+    }
+
+    #[cfg(feature = "nightly")]
+    allocator!(core::alloc::Allocator, core::alloc::AllocError);
+
+    // NOTE this conflicts with the implementation above when the `nightly` feature of
+    // `allocator-api2` is enabled, as its `Allocator` trait is then the one in `core`
+    #[cfg(feature = "allocator-api2")]
+    allocator!(
+        allocator_api2::alloc::Allocator,
+        allocator_api2::alloc::AllocError
+    );
+
+    impl<const FLL: usize, const POOLS: usize, const SL: usize> TlsfCell<'_, FLL, POOLS, SL>
+    where
+        Sl<SL>: SecondLevel,
+    {
+        fn allocate_bytes(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+            if layout.size() == 0 {
+                return Some(dangling(layout));
+            }
+
+            let alloc = unsafe { self.tlsf() }.memalign(layout)?;
+            Some(bytes(alloc))
+        }
+
+        fn allocate_zeroed_bytes(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+            if layout.size() == 0 {
+                return Some(dangling(layout));
+            }
+
+            let alloc = unsafe { self.tlsf() }.memalign_zeroed(layout)?;
+            Some(bytes(alloc))
+        }
+
+        unsafe fn deallocate_bytes(&self, ptr: NonNull<u8>, layout: Layout) {
+            if layout.size() != 0 {
+                self.tlsf().free(ptr.cast())
+            }
+        }
+
+        /// Resizes the block in place, when possible, and moves it otherwise
+        unsafe fn resize_bytes(
+            &self,
+            ptr: NonNull<u8>,
+            old_layout: Layout,
+            new_layout: Layout,
+        ) -> Option<NonNull<[u8]>> {
+            if old_layout.size() == 0 {
+                return self.allocate_bytes(new_layout);
+            }
+
+            if new_layout.size() == 0 {
+                self.deallocate_bytes(ptr, old_layout);
+                return Some(dangling(new_layout));
+            }
+
+            let alloc = self.tlsf().realloc(ptr.cast(), new_layout, usize::MAX)?;
+            Some(bytes(alloc))
+        }
+
+        unsafe fn grow_zeroed_bytes(
+            &self,
+            ptr: NonNull<u8>,
+            old_layout: Layout,
+            new_layout: Layout,
+        ) -> Option<NonNull<[u8]>> {
+            let alloc = self.resize_bytes(ptr, old_layout, new_layout)?;
+            let start = alloc.cast::<u8>().as_ptr().add(old_layout.size());
+            start.write_bytes(0, alloc.len() - old_layout.size());
+            Some(alloc)
+        }
+    }
+
+    fn bytes<T>(alloc: &mut [T]) -> NonNull<[u8]> {
+        let len = mem::size_of_val(alloc);
+        NonNull::slice_from_raw_parts(NonNull::from(alloc).cast(), len)
+    }
+
+    fn dangling(layout: Layout) -> NonNull<[u8]> {
+        // `align` is never zero
+        let ptr = unsafe { NonNull::new_unchecked(sptr::invalid_mut(layout.align())) };
+        NonNull::slice_from_raw_parts(ptr, 0)
+    }
+
+    #[cfg(feature = "nightly")]
+    #[cfg(test)]
+    mod nightly_tests {
+        use core::alloc::Allocator;
+        use core::mem::MaybeUninit;
+        use std::boxed::Box;
+        use std::vec::Vec;
+
+        use super::*;
+        use crate::Tlsf;
+
+        #[test]
+        fn collections() {
+            let mut memory = [MaybeUninit::uninit(); 256];
+            let mut tlsf = Tlsf::<2>::empty();
+            tlsf.initialize(&mut memory);
+            let tlsf = TlsfCell::new(tlsf);
+
+            let mut xs = Vec::new_in(&tlsf);
+            for i in 0..64 {
+                xs.push(i);
+            }
+            xs.truncate(4);
+            xs.shrink_to_fit();
+            assert_eq!([0, 1, 2, 3], *xs);
+
+            let boxed = Box::new_in(42_u64, &tlsf);
+            assert_eq!(0, &*boxed as *const u64 as usize % 8);
+            drop(boxed);
+            drop(xs);
+
+            let mut tlsf = tlsf.into_inner();
+            assert_eq!(1, tlsf.free_blocks().len());
+            assert!(tlsf.malloc(1.try_into().unwrap()).is_some());
+        }
+
+        #[test]
+        fn zero_sized() {
+            let mut memory = [MaybeUninit::uninit(); 16];
+            let mut tlsf = Tlsf::<1>::empty();
+            tlsf.initialize(&mut memory);
+            let tlsf = TlsfCell::new(tlsf);
+
+            let units = (0..1024)
+                .map(|_| Box::new_in((), &tlsf))
+                .collect::<Vec<_>>();
+            let empty = Vec::<u64, _>::with_capacity_in(0, &tlsf);
+            drop(units);
+            drop(empty);
+
+            let mut tlsf = tlsf;
+            assert_eq!(1, tlsf.get_mut().free_blocks().len());
+        }
+
+        #[test]
+        fn grow_zeroed() {
+            let mut memory = [MaybeUninit::uninit(); 64];
+            let mut tlsf = Tlsf::<2>::empty();
+            tlsf.initialize(&mut memory);
+            let tlsf = TlsfCell::new(tlsf);
+            let allocator = &tlsf;
+
+            let old_layout = Layout::new::<[u8; 4]>();
+            let new_layout = Layout::new::<[u8; 64]>();
+            unsafe {
+                let ptr = allocator.allocate(old_layout).unwrap().cast::<u8>();
+                ptr.as_ptr().write_bytes(!0, old_layout.size());
+
+                let grown = allocator.grow_zeroed(ptr, old_layout, new_layout).unwrap();
+                let bytes = grown.as_ref();
+                assert!(bytes.len() >= new_layout.size());
+                assert!(bytes[..4].iter().all(|byte| *byte == !0));
+                assert!(bytes[4..].iter().all(|byte| *byte == 0));
+
+                allocator.deallocate(grown.cast(), new_layout);
+            }
+        }
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[cfg(test)]
+    mod allocator_api2_tests {
+        use core::mem::MaybeUninit;
+
+        use allocator_api2::boxed::Box;
+        use allocator_api2::vec::Vec;
+        use hashbrown::HashMap;
+
+        use crate::{Tlsf, TlsfCell};
+
+        #[test]
+        fn collections() {
+            let mut memory = [MaybeUninit::uninit(); 1024];
+            let mut tlsf = Tlsf::<6>::empty();
+            tlsf.initialize(&mut memory);
+            let tlsf = TlsfCell::new(tlsf);
+
+            let mut xs = Vec::new_in(&tlsf);
+            xs.extend(0..64);
+            xs.truncate(4);
+            xs.shrink_to_fit();
+            assert_eq!([0, 1, 2, 3], *xs);
+
+            let boxed = Box::new_in(42_u64, &tlsf);
+            assert_eq!(0, &*boxed as *const u64 as usize % 8);
+
+            let mut map = HashMap::new_in(&tlsf);
+            for i in 0..32 {
+                map.insert(i, i * 2);
+            }
+            assert_eq!(Some(&20), map.get(&10));
+
+            drop((xs, boxed, map));
+            let mut tlsf = tlsf;
+            assert_eq!(1, tlsf.get_mut().free_blocks().len());
+        }
+    }
+}
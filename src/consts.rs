@@ -1,44 +1,95 @@
-use crate::block::{FreeBlock, Offset};
+use crate::block::{FreeBlock, Offset, UsedBlock};
 use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::Size;
 
+// granularity of block sizes and alignment of block bodies, selected with the `align-8` and
+// `align-16` features; the largest alignment wins when both features are enabled
+#[cfg(not(any(feature = "align-8", feature = "align-16")))]
 pub const BLOCK_ALIGN_LOG2: u8 = 2;
+#[cfg(all(feature = "align-8", not(feature = "align-16")))]
+pub const BLOCK_ALIGN_LOG2: u8 = 3;
+#[cfg(feature = "align-16")]
+pub const BLOCK_ALIGN_LOG2: u8 = 4;
 pub const BLOCK_ALIGN: u8 = 1 << BLOCK_ALIGN_LOG2;
 
-// NOTE cannot be greater beacuse `sl_bitmaps` is `[u16; _]`
-pub const SLL_LOG2: u8 = 4;
+// written in the last word of the body of a used block by the `hardening` feature
+#[cfg(feature = "hardening")]
+pub const CANARY: u32 = 0xc0ff_ee42;
+// bytes of the body of a used block taken by the canary and thus not available to the allocation
+#[cfg(feature = "hardening")]
+pub const CANARY_SIZE: Size = core::mem::size_of::<u32>() as Size;
+#[cfg(not(feature = "hardening"))]
+pub const CANARY_SIZE: Size = 0;
+// fills the body of a free block, past its free list links, when the `hardening` feature is enabled
+#[cfg(feature = "hardening")]
+pub const POISON: u32 = 0xdead_beef;
+// replaces the canary of a block held in the quarantine of the `hardening` feature
+#[cfg(feature = "hardening")]
+pub const QUARANTINED: u32 = 0x0bad_f4ee;
 
-// all second level indices are smaller than this value
-pub const SLL: u8 = 1 << SLL_LOG2;
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    #[cfg(all(
+        test,
+        not(any(
+            feature = "wide-offsets",
+            feature = "wide-sizes",
+            feature = "hardening"
+        ))
+    ))]
+    const HEADER_SIZE: usize = core::mem::size_of::<Header<FLL, SL>>();
 
-// For small values of the `fl` index we would have to split sizes in the range
-// of e.g. `4..8` into `SLL` smaller ranges. That doesn't make much sense so
-// instead we merge all the rows with `fl < LOWER_SIZE_THRESHOLD` into a single
-// row.
-pub const MIN_FLL: u8 = SLL_LOG2 + BLOCK_ALIGN_LOG2;
-pub const LOWER_SIZE_THRESHOLD: u16 = 1 << MIN_FLL;
+    // all second level indices are smaller than `SL` (`SLL` for short)
+    pub(crate) const SLL_LOG2: u8 = SL.trailing_zeros() as u8;
 
-impl<const FLL: usize> Header<FLL> {
-    #[cfg(test)]
-    const HEADER_SIZE: usize = core::mem::size_of::<Header<FLL>>();
+    // For small values of the `fl` index we would have to split sizes in the range
+    // of e.g. `4..8` into `SLL` smaller ranges. That doesn't make much sense so
+    // instead we merge all the rows with `fl < LOWER_SIZE_THRESHOLD` into a single
+    // row.
+    pub(crate) const MIN_FLL: u8 = Self::SLL_LOG2 + BLOCK_ALIGN_LOG2;
+    pub(crate) const LOWER_SIZE_THRESHOLD: Size = 1 << Self::MIN_FLL;
 
     pub(crate) const REAL_FLL: u8 = {
         assert!(FLL > 0);
-        assert!(FLL < 12);
-        FLL as u8 + MIN_FLL - 1
+        // the largest size class must be representable as a `Size`. With the default `u16` sizes
+        // and `SL = 16`, `FLL` is at most 11
+        assert!(FLL as u32 + Self::MIN_FLL as u32 - 1 <= Size::BITS);
+        FLL as u8 + Self::MIN_FLL - 1
     };
 
-    pub(crate) const UPPER_SIZE_THRESHOLD: u16 =
-        ((1u32 << Self::REAL_FLL) - BLOCK_ALIGN as u32) as u16;
-    pub const MAX_ALLOC_SIZE: u16 = {
-        let step = 1 << (Self::REAL_FLL - SLL_LOG2 - 1);
-        let step = if step <= 4 { 4 } else { step };
+    pub(crate) const UPPER_SIZE_THRESHOLD: Size =
+        ((1u64 << Self::REAL_FLL) - BLOCK_ALIGN as u64) as Size;
+    pub const MAX_ALLOC_SIZE: Size = {
+        let step = 1 << (Self::REAL_FLL - Self::SLL_LOG2 - 1);
+        let step = if step <= BLOCK_ALIGN as u64 {
+            BLOCK_ALIGN as u64
+        } else {
+            step
+        };
 
-        ((1u32 << Self::REAL_FLL) - step) as u16
+        ((1u64 << Self::REAL_FLL) - step) as Size
     };
 }
 
-pub const MAX_USABLE_SIZE: u16 = u16::MAX & !0b11;
+pub const MAX_USABLE_SIZE: Size = !(BLOCK_ALIGN as Size - 1);
 
+// largest `FLL` that the default `SL` of 16 allows with the selected size type and block granularity
+#[cfg(test)]
+pub const MAX_FLL: usize = Size::BITS as usize - 4 - BLOCK_ALIGN_LOG2 as usize + 1;
+
+// a used block must be able to hold the header of a free block once it's freed
+pub const MIN_USABLE_SIZE: Size = (FreeBlock::HEADER_SIZE - UsedBlock::HEADER_SIZE) as Size;
+
+// all the pools share this offset space: the header of the block with the largest offset must still
+// fit in it
+pub const MAX_OFFSET_SPACE: usize =
+    Offset::MAX_UNCOMPRESSED_VALUE.saturating_add(FreeBlock::HEADER_SIZE as usize);
+
+// size of the memory that a single pool, the first one, needs to fill the whole offset space
+#[cfg(all(any(fuzzing, test), not(feature = "wide-offsets")))]
 pub const MAX_POOL_SIZE: usize =
     Offset::MAX_UNCOMPRESSED_VALUE +
     FreeBlock::HEADER_SIZE as usize +
@@ -48,8 +99,11 @@ pub const MAX_POOL_SIZE: usize =
 // check documented values
 #[cfg(test)]
 mod tests {
+    #[allow(unused_imports)] // every test is specific to some layouts
     use super::*;
 
+    // the documented values assume the default 4-byte block granularity
+    #[cfg(not(any(feature = "align-8", feature = "align-16")))]
     #[test]
     fn max_alloc_size() {
         assert_eq!(60, Header::<1>::MAX_ALLOC_SIZE);
@@ -65,18 +119,73 @@ mod tests {
         assert_eq!(63_488, Header::<11>::MAX_ALLOC_SIZE);
     }
 
+    #[cfg(feature = "wide-sizes")]
+    // the documented values assume the default 4-byte block granularity
+    #[cfg(not(any(feature = "align-8", feature = "align-16")))]
+    #[test]
+    fn max_alloc_size_wide() {
+        assert_eq!(126_976, Header::<12>::MAX_ALLOC_SIZE);
+        assert_eq!(4_160_749_568, Header::<27>::MAX_ALLOC_SIZE);
+    }
+
+    // the documented values assume the default offset and size types and a header without the
+    // state of the `hardening` feature
+    #[cfg(not(any(
+        feature = "wide-offsets",
+        feature = "wide-sizes",
+        feature = "hardening"
+    )))]
     #[test]
     fn header_size() {
-        assert_eq!(36, Header::<1>::HEADER_SIZE);
-        assert_eq!(72, Header::<2>::HEADER_SIZE);
-        assert_eq!(104, Header::<3>::HEADER_SIZE);
-        assert_eq!(140, Header::<4>::HEADER_SIZE);
-        assert_eq!(172, Header::<5>::HEADER_SIZE);
-        assert_eq!(208, Header::<6>::HEADER_SIZE);
-        assert_eq!(240, Header::<7>::HEADER_SIZE);
-        assert_eq!(276, Header::<8>::HEADER_SIZE);
-        assert_eq!(308, Header::<9>::HEADER_SIZE);
-        assert_eq!(344, Header::<10>::HEADER_SIZE);
-        assert_eq!(376, Header::<11>::HEADER_SIZE);
+        assert_eq!(64, Header::<1>::HEADER_SIZE);
+        assert_eq!(100, Header::<2>::HEADER_SIZE);
+        assert_eq!(132, Header::<3>::HEADER_SIZE);
+        assert_eq!(168, Header::<4>::HEADER_SIZE);
+        assert_eq!(200, Header::<5>::HEADER_SIZE);
+        assert_eq!(236, Header::<6>::HEADER_SIZE);
+        assert_eq!(268, Header::<7>::HEADER_SIZE);
+        assert_eq!(304, Header::<8>::HEADER_SIZE);
+        assert_eq!(336, Header::<9>::HEADER_SIZE);
+        assert_eq!(372, Header::<10>::HEADER_SIZE);
+        assert_eq!(404, Header::<11>::HEADER_SIZE);
+    }
+
+    // the documented values assume the default 4-byte block granularity
+    #[cfg(not(any(feature = "align-8", feature = "align-16")))]
+    #[test]
+    fn max_alloc_size_sl() {
+        assert_eq!(12, Header::<1, 4>::MAX_ALLOC_SIZE);
+        assert_eq!(1_792, Header::<8, 4>::MAX_ALLOC_SIZE);
+        assert_eq!(57_344, Header::<13, 4>::MAX_ALLOC_SIZE);
+
+        assert_eq!(28, Header::<1, 8>::MAX_ALLOC_SIZE);
+        assert_eq!(3_840, Header::<8, 8>::MAX_ALLOC_SIZE);
+        assert_eq!(61_440, Header::<12, 8>::MAX_ALLOC_SIZE);
+
+        assert_eq!(124, Header::<1, 32>::MAX_ALLOC_SIZE);
+        assert_eq!(16_128, Header::<8, 32>::MAX_ALLOC_SIZE);
+        assert_eq!(64_512, Header::<10, 32>::MAX_ALLOC_SIZE);
+    }
+
+    // the documented values assume the default offset and size types and a header without the
+    // state of the `hardening` feature
+    #[cfg(not(any(
+        feature = "wide-offsets",
+        feature = "wide-sizes",
+        feature = "hardening"
+    )))]
+    #[test]
+    fn header_size_sl() {
+        assert_eq!(40, Header::<1, 4>::HEADER_SIZE);
+        assert_eq!(104, Header::<8, 4>::HEADER_SIZE);
+        assert_eq!(148, Header::<13, 4>::HEADER_SIZE);
+
+        assert_eq!(48, Header::<1, 8>::HEADER_SIZE);
+        assert_eq!(168, Header::<8, 8>::HEADER_SIZE);
+        assert_eq!(236, Header::<12, 8>::HEADER_SIZE);
+
+        assert_eq!(100, Header::<1, 32>::HEADER_SIZE);
+        assert_eq!(576, Header::<8, 32>::HEADER_SIZE);
+        assert_eq!(712, Header::<10, 32>::HEADER_SIZE);
     }
 }
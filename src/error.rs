@@ -0,0 +1,233 @@
+use core::fmt;
+
+#[allow(unused_imports)] // used by API docs
+use crate::{Block, Size, Tlsf};
+
+/// The reason why [`Tlsf::try_malloc`] or [`Tlsf::try_memalign`] failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocError {
+    /// No memory pool has been given to the allocator (see [`Tlsf::initialize`])
+    Uninitialized,
+
+    /// The requested size is zero
+    ZeroSize,
+
+    /// The requested size or alignment does not fit in a [`Size`]
+    UnsupportedLayout,
+
+    /// The request, including the padding needed to meet its alignment, exceeds the
+    /// `MAX_ALLOC_SIZE` of the allocator
+    TooLarge,
+
+    /// The free memory, in total, is smaller than the request
+    OutOfMemory {
+        /// Total usable size, in bytes, of all the free blocks
+        free_bytes: usize,
+    },
+
+    /// There's enough free memory, in total, to fit the request but no single free block is
+    /// large enough
+    Fragmented {
+        /// Total usable size, in bytes, of all the free blocks
+        free_bytes: usize,
+    },
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::Uninitialized => f.write_str("allocator has no memory pool"),
+            AllocError::ZeroSize => f.write_str("requested size is zero"),
+            AllocError::UnsupportedLayout => {
+                f.write_str("requested size or alignment is out of range")
+            }
+            AllocError::TooLarge => f.write_str("request exceeds the maximum allocation size"),
+            AllocError::OutOfMemory { free_bytes } => {
+                write!(f, "out of memory ({free_bytes} bytes free)")
+            }
+            AllocError::Fragmented { free_bytes } => {
+                write!(f, "no free block is large enough ({free_bytes} bytes free)")
+            }
+        }
+    }
+}
+
+/// The reason why [`Tlsf::try_free`] rejected a pointer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FreeError {
+    /// The pointer does not point into any of the pools of the allocator
+    ForeignPointer,
+
+    /// The pointer is not aligned to the block granularity: 4 bytes, or 8 / 16 bytes when the
+    /// `align-8` / `align-16` feature is enabled
+    Misaligned,
+
+    /// The pointer does not point at the start of the body of a block, e.g. it points into the
+    /// middle of an allocation, or the block header does not agree with its neighbors
+    NotABlock,
+
+    /// The header in front of the pointer is marked as free, i.e. the block was already freed
+    DoubleFree,
+}
+
+impl fmt::Display for FreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FreeError::ForeignPointer => "pointer does not belong to the allocator",
+            FreeError::Misaligned => "pointer is misaligned",
+            FreeError::NotABlock => "pointer does not point at the start of a block",
+            FreeError::DoubleFree => "block is already free",
+        })
+    }
+}
+
+/// A heap invariant that [`Tlsf::check`] found to be violated, or heap damage that the `hardening`
+/// feature detected
+///
+/// `block` is the compressed offset of the offending block, see [`Block::offset`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// The size of the block is not a multiple of the block granularity, is too small for a free
+    /// block or makes the block extend past the end of its pool
+    BlockSize {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The block is flagged as the last block of its pool but isn't, or vice versa
+    LastPhysBlock {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The `prev_phys_block` link of the block does not point to the block that physically
+    /// precedes it
+    PrevPhysBlock {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The block and the block that physically precedes it are both free; they should have been
+    /// merged
+    Uncoalesced {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The free block is not linked in the free list its size maps to
+    FreeList {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The free block links to a block that is not free or that does not link back to it; the
+    /// `safe-linking` feature reports it when a block leaves its free list or the quarantine
+    FreeLink {
+        /// Offset of the block
+        block: usize,
+    },
+
+    /// The bitmap bit of a free list does not match the emptiness of the list
+    Bitmap {
+        /// First level index of the free list
+        fl: u8,
+        /// Second level index of the free list
+        sl: u8,
+    },
+
+    /// The canary that follows the allocation was overwritten, i.e. a buffer overrun; only
+    /// reported by the `hardening` feature
+    Overrun {
+        /// Offset of the used block
+        block: usize,
+    },
+
+    /// The body of the free block was written to after the block was freed; only reported by the
+    /// `hardening` feature
+    UseAfterFree {
+        /// Offset of the free block
+        block: usize,
+    },
+
+    /// The block was freed while held in the quarantine; only reported by the `hardening` feature
+    DoubleFree {
+        /// Offset of the quarantined block
+        block: usize,
+    },
+
+    /// The header of the block does not match its checksum; only reported by the
+    /// `header-checksum` feature
+    HeaderChecksum {
+        /// Offset of the used block
+        block: usize,
+    },
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Corruption::BlockSize { block } => write!(f, "block {block} has an invalid size"),
+            Corruption::LastPhysBlock { block } => {
+                write!(f, "block {block} has an invalid last block flag")
+            }
+            Corruption::PrevPhysBlock { block } => {
+                write!(f, "block {block} does not link to its physical predecessor")
+            }
+            Corruption::Uncoalesced { block } => {
+                write!(f, "free block {block} follows another free block")
+            }
+            Corruption::FreeList { block } => {
+                write!(f, "free block {block} is not in the free list of its size")
+            }
+            Corruption::FreeLink { block } => {
+                write!(f, "free block {block} has an invalid free list link")
+            }
+            Corruption::Bitmap { fl, sl } => {
+                write!(f, "bitmap does not match free list ({fl}, {sl})")
+            }
+            Corruption::Overrun { block } => {
+                write!(f, "the canary of used block {block} was overwritten")
+            }
+            Corruption::UseAfterFree { block } => {
+                write!(f, "free block {block} was written to after it was freed")
+            }
+            Corruption::DoubleFree { block } => {
+                write!(f, "quarantined block {block} was freed again")
+            }
+            Corruption::HeaderChecksum { block } => {
+                write!(
+                    f,
+                    "the header of used block {block} does not match its checksum"
+                )
+            }
+        }
+    }
+}
+
+/// The reason why [`Tlsf::try_initialize`] or [`Tlsf::try_add_pool`] ignored the given memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolError {
+    /// [`Tlsf::try_initialize`] was called on an allocator that already manages memory
+    AlreadyInitialized,
+
+    /// `POOLS` pools have already been added to the allocator
+    NoRoom,
+
+    /// The pools added so far already fill the offset space: 256 KiB unless the `wide-offsets`
+    /// feature is enabled
+    OffsetSpaceExhausted,
+
+    /// The memory is too small to hold a single memory block
+    TooSmall,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PoolError::AlreadyInitialized => "allocator is already initialized",
+            PoolError::NoRoom => "allocator can't hold more pools",
+            PoolError::OffsetSpaceExhausted => "allocator can't manage more memory",
+            PoolError::TooSmall => "memory is too small to hold a block",
+        })
+    }
+}
@@ -0,0 +1,268 @@
+//! A [`GlobalAlloc`] adapter for [`Tlsf`]
+//!
+//! The application decides how the allocator is synchronized, by implementing [`Lock`], and
+//! whether `realloc` may copy memory, by picking a [`ReallocPolicy`].
+//!
+//! ```
+//! use core::mem::MaybeUninit;
+//! use std::sync::Mutex;
+//!
+//! use tlsf::global::{GlobalTlsf, Lock, ReallocPolicy};
+//!
+//! struct MutexLock(Mutex<()>);
+//!
+//! // SAFETY: the mutex serializes all the `with_lock` calls
+//! unsafe impl Lock for MutexLock {
+//!     fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+//!         let _guard = self.0.lock().unwrap();
+//!         f()
+//!     }
+//! }
+//!
+//! // #[global_allocator]
+//! static HEAP: GlobalTlsf<MutexLock, 2> =
+//!     GlobalTlsf::new(MutexLock(Mutex::new(())), ReallocPolicy::InPlace);
+//!
+//! static mut MEMORY: [MaybeUninit<u32>; 256] = [MaybeUninit::uninit(); 256];
+//!
+//! // SAFETY: `MEMORY` is not accessed anywhere else
+//! HEAP.initialize(unsafe { &mut *core::ptr::addr_of_mut!(MEMORY) });
+//! ```
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use crate::sl::{SecondLevel, Sl};
+use crate::Tlsf;
+
+/// Mutual exclusion mechanism that guards the allocator of a [`GlobalTlsf`]
+///
+/// Typical implementations use a spin mutex, a critical section or mask interrupts.
+///
+/// # Safety
+///
+/// - `with_lock` MUST NOT run `f` while another invocation of `with_lock` on the same lock is
+///   running its `f`, even when that other invocation happens in a different thread or in an
+///   interrupt handler
+pub unsafe trait Lock {
+    /// Runs `f` with exclusive access to the resource guarded by the lock
+    fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R;
+}
+
+/// What [`GlobalTlsf`] does when a `realloc` request can't be satisfied in place
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReallocPolicy {
+    /// Fail the request; `realloc` then executes in bounded constant time (`O(1)`)
+    InPlace,
+
+    /// Move the allocation to a new block, if that involves copying no more than `max_copy`
+    /// bytes, and fail the request otherwise
+    Copy {
+        /// Maximum number of bytes copied when moving an allocation
+        max_copy: usize,
+    },
+}
+
+/// A [`Tlsf`] allocator that can be used as a `#[global_allocator]`
+pub struct GlobalTlsf<L, const FLL: usize, const POOLS: usize = 1, const SL: usize = 16>
+where
+    Sl<SL>: SecondLevel,
+{
+    lock: L,
+    realloc_policy: ReallocPolicy,
+    tlsf: UnsafeCell<Tlsf<'static, FLL, POOLS, SL>>,
+}
+
+// SAFETY: all accesses to `tlsf` happen within `Lock::with_lock`
+unsafe impl<L, const FLL: usize, const POOLS: usize, const SL: usize> Sync
+    for GlobalTlsf<L, FLL, POOLS, SL>
+where
+    L: Lock + Sync,
+    Sl<SL>: SecondLevel,
+{
+}
+
+impl<L, const FLL: usize, const POOLS: usize, const SL: usize> GlobalTlsf<L, FLL, POOLS, SL>
+where
+    L: Lock,
+    Sl<SL>: SecondLevel,
+{
+    /// Creates a new allocator with no associated memory
+    ///
+    /// NOTE: all allocation requests fail until memory is given to the allocator with
+    /// [`GlobalTlsf::initialize`]
+    pub const fn new(lock: L, realloc_policy: ReallocPolicy) -> Self {
+        Self {
+            lock,
+            realloc_policy,
+            tlsf: UnsafeCell::new(Tlsf::empty()),
+        }
+    }
+
+    /// Gives the allocator a chunk of memory to manage
+    ///
+    /// See [`Tlsf::initialize`] for details
+    pub fn initialize(&self, memory: &'static mut [MaybeUninit<u32>]) {
+        self.with(|tlsf| tlsf.initialize(memory))
+    }
+
+    /// Gives the allocator an additional chunk of memory to manage
+    ///
+    /// See [`Tlsf::add_pool`] for details
+    pub fn add_pool(&self, memory: &'static mut [MaybeUninit<u32>]) -> bool {
+        self.with(|tlsf| tlsf.add_pool(memory))
+    }
+
+    /// Runs `f` with exclusive access to the inner allocator, e.g. to inspect its blocks
+    ///
+    /// NOTE: allocating from the global allocator within `f` results in a deadlock, or a panic,
+    /// depending on the `Lock` implementation
+    pub fn with<R>(&self, f: impl FnOnce(&mut Tlsf<'static, FLL, POOLS, SL>) -> R) -> R {
+        // SAFETY: `Lock` guarantees exclusive access to `tlsf`
+        self.lock.with_lock(|| f(unsafe { &mut *self.tlsf.get() }))
+    }
+}
+
+unsafe impl<L, const FLL: usize, const POOLS: usize, const SL: usize> GlobalAlloc
+    for GlobalTlsf<L, FLL, POOLS, SL>
+where
+    L: Lock,
+    Sl<SL>: SecondLevel,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with(|tlsf| {
+            tlsf.memalign(layout)
+                .map(|alloc| alloc.as_mut_ptr().cast())
+                .unwrap_or(ptr::null_mut())
+        })
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.with(|tlsf| {
+            tlsf.memalign_zeroed(layout)
+                .map(|alloc| alloc.as_mut_ptr().cast())
+                .unwrap_or(ptr::null_mut())
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            self.with(|tlsf| tlsf.free(ptr.cast()))
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Some(ptr) = NonNull::new(ptr) else {
+            return ptr::null_mut();
+        };
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let max_copy = match self.realloc_policy {
+            ReallocPolicy::InPlace => 0,
+            ReallocPolicy::Copy { max_copy } => max_copy,
+        };
+
+        self.with(|tlsf| {
+            tlsf.realloc(ptr.cast(), new_layout, max_copy)
+                .map(|alloc| alloc.as_mut_ptr().cast())
+                .unwrap_or(ptr::null_mut())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    struct MutexLock(Mutex<()>);
+
+    unsafe impl Lock for MutexLock {
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let _guard = self.0.lock().unwrap();
+            f()
+        }
+    }
+
+    fn heap(realloc_policy: ReallocPolicy) -> GlobalTlsf<MutexLock, 2> {
+        let heap = GlobalTlsf::new(MutexLock(Mutex::new(())), realloc_policy);
+        let memory = Box::leak(vec![MaybeUninit::uninit(); 64].into_boxed_slice());
+        heap.initialize(memory);
+        heap
+    }
+
+    #[test]
+    fn uninitialized() {
+        let heap = GlobalTlsf::<_, 1>::new(MutexLock(Mutex::new(())), ReallocPolicy::InPlace);
+        assert!(unsafe { heap.alloc(Layout::new::<u32>()) }.is_null());
+    }
+
+    #[test]
+    fn alloc_dealloc() {
+        let heap = heap(ReallocPolicy::InPlace);
+        let layout = Layout::new::<u64>();
+
+        let ptr = unsafe { heap.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(0, ptr as usize % layout.align());
+        unsafe { ptr.cast::<u64>().write(!0) }
+
+        unsafe { heap.dealloc(ptr, layout) }
+        assert_eq!(1, heap.with(|tlsf| tlsf.free_blocks().len()));
+    }
+
+    #[test]
+    fn alloc_zeroed() {
+        let heap = heap(ReallocPolicy::InPlace);
+        let layout = Layout::new::<[u8; 32]>();
+
+        let ptr = unsafe { heap.alloc(layout) };
+        unsafe { ptr.write_bytes(!0, layout.size()) }
+        unsafe { heap.dealloc(ptr, layout) }
+
+        let ptr = unsafe { heap.alloc_zeroed(layout) };
+        let bytes = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+        assert!(bytes.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn realloc_in_place() {
+        let heap = heap(ReallocPolicy::InPlace);
+        let layout = Layout::new::<[u8; 8]>();
+
+        let first = unsafe { heap.alloc(layout) };
+        let second = unsafe { heap.alloc(layout) };
+
+        // growing `second` in place is possible
+        let grown = unsafe { heap.realloc(second, layout, 16) };
+        assert_eq!(second, grown);
+
+        // growing `first` would require moving it
+        assert!(unsafe { heap.realloc(first, layout, 16) }.is_null());
+    }
+
+    #[test]
+    fn realloc_copy() {
+        let heap = heap(ReallocPolicy::Copy { max_copy: 8 });
+        let layout = Layout::new::<[u8; 8]>();
+
+        let first = unsafe { heap.alloc(layout) };
+        let _second = unsafe { heap.alloc(layout) };
+        unsafe { first.write_bytes(42, layout.size()) }
+
+        let moved = unsafe { heap.realloc(first, layout, 16) };
+        assert!(!moved.is_null());
+        assert_ne!(first, moved);
+        let bytes = unsafe { core::slice::from_raw_parts(moved, layout.size()) };
+        assert!(bytes.iter().all(|byte| *byte == 42));
+
+        // keep `moved` from growing in place; `first`'s old block is too small for this
+        let layout = Layout::new::<[u8; 16]>();
+        let _third = unsafe { heap.alloc(layout) };
+
+        // exceeds the copy budget
+        assert!(unsafe { heap.realloc(moved, layout, 32) }.is_null());
+    }
+}
@@ -3,34 +3,191 @@ use crate::block::Anchor;
 #[cfg(test)]
 use crate::block::FreeBlock;
 use crate::block::Offset;
-use crate::consts;
+#[cfg(test)]
+use crate::mapping::Indices;
+#[cfg(feature = "hardening")]
+use crate::ops::Quarantine;
+use crate::sl::sealed::Sealed;
+use crate::sl::{SecondLevel, Sl};
+#[cfg(feature = "hardening")]
+use crate::Corruption;
+use crate::{Size, Stats};
 
 #[repr(align(4))]
-pub struct Header<const FLL: usize> {
-    fl_bitmap: u16,
-    sl_bitmaps: [u16; FLL],
-    free_lists: [FirstLevel; FLL],
+pub struct Header<const FLL: usize, const SL: usize = 16>
+where
+    Sl<SL>: SecondLevel,
+{
+    fl_bitmap: Size,
+    sl_bitmaps: [SlBitmap<SL>; FLL],
+    free_lists: [[FreeList; SL]; FLL],
+    counters: Counters,
+    #[cfg(feature = "hardening")]
+    hook: fn(Corruption),
+    #[cfg(feature = "hardening")]
+    quarantine: Quarantine,
+    #[cfg(feature = "safe-linking")]
+    secret: u32,
 }
 
-impl<const FLL: usize> Header<FLL> {
-    pub const fn new() -> Self {
-        const FREE_LIST: FreeList = None;
-        const FIRST_LEVEL: FirstLevel = [FREE_LIST; consts::SLL as usize];
+// byte counters; only with `wide-offsets` can the pools add up to more than 4 GiB
+#[cfg(not(feature = "wide-offsets"))]
+type Bytes = u32;
+#[cfg(feature = "wide-offsets")]
+type Bytes = usize;
+
+// `Bytes` and `Size` are both `u32` with `wide-sizes`
+#[allow(clippy::unnecessary_cast)]
+fn bytes(size: Size) -> Bytes {
+    size as Bytes
+}
+
+/// The counters behind `Stats`, narrower than the public fields to keep the header small
+///
+/// A block takes at least 8 bytes so the block counters can't overflow before the byte counters
+/// do; the request counters wrap around
+struct Counters {
+    free_bytes: Bytes,
+    used_bytes: Bytes,
+    free_blocks: u32,
+    used_blocks: u32,
+    allocs: u32,
+    frees: u32,
+    failed_allocs: u32,
+}
 
+impl Counters {
+    const ZERO: Self = Self {
+        free_bytes: 0,
+        used_bytes: 0,
+        free_blocks: 0,
+        used_blocks: 0,
+        allocs: 0,
+        frees: 0,
+        failed_allocs: 0,
+    };
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    pub const fn new() -> Self {
         Self {
             fl_bitmap: 0,
-            sl_bitmaps: [0; FLL],
-            free_lists: [FIRST_LEVEL; FLL],
+            sl_bitmaps: [<Sl<SL>>::EMPTY; FLL],
+            free_lists: [[None; SL]; FLL],
+            counters: Counters::ZERO,
+            #[cfg(feature = "hardening")]
+            hook: ignore_corruption,
+            #[cfg(feature = "hardening")]
+            quarantine: Quarantine::EMPTY,
+            #[cfg(feature = "safe-linking")]
+            secret: 0,
+        }
+    }
+
+    #[cfg(feature = "hardening")]
+    pub fn set_hook(&mut self, hook: fn(Corruption)) {
+        self.hook = hook;
+    }
+
+    #[cfg(feature = "hardening")]
+    pub fn quarantine(&self) -> &Quarantine {
+        &self.quarantine
+    }
+
+    #[cfg(feature = "hardening")]
+    pub fn quarantine_mut(&mut self) -> &mut Quarantine {
+        &mut self.quarantine
+    }
+
+    #[cfg(feature = "safe-linking")]
+    pub fn secret(&self) -> u32 {
+        self.secret
+    }
+
+    #[cfg(feature = "safe-linking")]
+    pub fn set_secret(&mut self, secret: u32) {
+        self.secret = secret;
+    }
+
+    /// Hands `corruption` to the hook set with `Tlsf::set_corruption_hook`
+    #[cfg(feature = "hardening")]
+    pub fn report(&self, corruption: Corruption) {
+        (self.hook)(corruption)
+    }
+
+    // `Bytes` is `usize` with `wide-offsets`
+    #[allow(clippy::unnecessary_cast)]
+    pub fn stats(&self) -> Stats {
+        let counters = &self.counters;
+        Stats {
+            free_bytes: counters.free_bytes as usize,
+            used_bytes: counters.used_bytes as usize,
+            free_blocks: counters.free_blocks as usize,
+            used_blocks: counters.used_blocks as usize,
+            allocs: counters.allocs as usize,
+            frees: counters.frees as usize,
+            failed_allocs: counters.failed_allocs as usize,
         }
     }
 
+    pub fn count_alloc(&mut self) {
+        self.counters.allocs = self.counters.allocs.wrapping_add(1);
+    }
+
+    pub fn count_failed_alloc(&mut self) {
+        self.counters.failed_allocs = self.counters.failed_allocs.wrapping_add(1);
+    }
+
+    pub fn count_free(&mut self) {
+        self.counters.frees = self.counters.frees.wrapping_add(1);
+    }
+
+    /// Accounts for a block of `size` usable bytes entering the free lists
+    pub fn track_free(&mut self, size: Size) {
+        let counters = &mut self.counters;
+        counters.free_bytes = counters.free_bytes.wrapping_add(bytes(size));
+        counters.free_blocks = counters.free_blocks.wrapping_add(1);
+    }
+
+    /// Accounts for a block of `size` usable bytes leaving the free lists
+    pub fn untrack_free(&mut self, size: Size) {
+        let counters = &mut self.counters;
+
+        #[cfg(any(fuzzing, test))]
+        debug_assert!(counters.free_bytes >= bytes(size) && counters.free_blocks > 0);
+
+        counters.free_bytes = counters.free_bytes.wrapping_sub(bytes(size));
+        counters.free_blocks = counters.free_blocks.wrapping_sub(1);
+    }
+
+    /// Accounts for a block of `size` usable bytes becoming used
+    pub fn track_used(&mut self, size: Size) {
+        let counters = &mut self.counters;
+        counters.used_bytes = counters.used_bytes.wrapping_add(bytes(size));
+        counters.used_blocks = counters.used_blocks.wrapping_add(1);
+    }
+
+    /// Accounts for a used block of `size` usable bytes being freed or resized
+    pub fn untrack_used(&mut self, size: Size) {
+        let counters = &mut self.counters;
+
+        #[cfg(any(fuzzing, test))]
+        debug_assert!(counters.used_bytes >= bytes(size) && counters.used_blocks > 0);
+
+        counters.used_bytes = counters.used_bytes.wrapping_sub(bytes(size));
+        counters.used_blocks = counters.used_blocks.wrapping_sub(1);
+    }
+
     /// # Safety
     /// - caller must perform bounds checks
     pub unsafe fn get_free_list(&self, fl: u8, sl: u8) -> FreeList {
         #[cfg(any(fuzzing, test))]
         debug_assert!(usize::from(fl) < FLL);
         #[cfg(any(fuzzing, test))]
-        debug_assert!(sl < consts::SLL);
+        debug_assert!(usize::from(sl) < SL);
 
         *self
             .free_lists
@@ -44,7 +201,7 @@ impl<const FLL: usize> Header<FLL> {
         #[cfg(any(fuzzing, test))]
         debug_assert!(usize::from(fl) < FLL);
         #[cfg(any(fuzzing, test))]
-        debug_assert!(sl < consts::SLL);
+        debug_assert!(usize::from(sl) < SL);
 
         *self
             .free_lists
@@ -53,27 +210,27 @@ impl<const FLL: usize> Header<FLL> {
     }
 
     pub fn clear_fl_bit(&mut self, fl: u8) {
-        self.fl_bitmap &= !1u16.wrapping_shl(fl.into());
+        self.fl_bitmap &= !Size::wrapping_shl(1, fl.into());
     }
 
     pub fn set_fl_bit(&mut self, fl: u8) {
-        self.fl_bitmap |= 1u16.wrapping_shl(fl.into());
+        self.fl_bitmap |= Size::wrapping_shl(1, fl.into());
     }
 
     #[cfg(test)]
     pub fn is_fl_bit_set(&self, fl: u8) -> bool {
-        let mask = 1u16.wrapping_shl(fl.into());
+        let mask = Size::wrapping_shl(1, fl.into());
         self.fl_bitmap & mask == mask
     }
 
-    pub fn suitable_fls(&self, fl: u8) -> u16 {
-        let mask = (!0u16).wrapping_shl(fl.into());
+    pub fn suitable_fls(&self, fl: u8) -> Size {
+        let mask = Size::MAX.wrapping_shl(fl.into());
         self.fl_bitmap & mask
     }
 
     /// # Safety
     /// - caller must perform bounds checks
-    pub unsafe fn get_sl_bitmap(&self, fl: u8) -> u16 {
+    pub unsafe fn get_sl_bitmap(&self, fl: u8) -> SlBitmap<SL> {
         *self.sl_bitmap(fl)
     }
 
@@ -81,39 +238,47 @@ impl<const FLL: usize> Header<FLL> {
     /// - caller must perform bounds checks
     pub unsafe fn clear_sl_bit(&mut self, fl: u8, sl: u8) {
         let sl_bitmap = self.sl_bitmap_mut(fl);
-        *sl_bitmap &= !1u16.wrapping_shl(sl.into());
+        *sl_bitmap = <Sl<SL>>::from_bits((*sl_bitmap).into() & !1u32.wrapping_shl(sl.into()));
     }
 
     /// # Safety
     /// - caller must perform bounds checks
     pub unsafe fn set_sl_bit(&mut self, fl: u8, sl: u8) {
         let sl_bitmap = self.sl_bitmap_mut(fl);
-        *sl_bitmap |= 1u16.wrapping_shl(sl.into());
+        *sl_bitmap = <Sl<SL>>::from_bits((*sl_bitmap).into() | 1u32.wrapping_shl(sl.into()));
     }
 
     /// # Safety
     /// - caller must perform bounds checks
     #[cfg(test)]
     pub unsafe fn is_sl_bit_set(&self, fl: u8, sl: u8) -> bool {
-        let mask = 1u16.wrapping_shl(sl.into());
-        *self.sl_bitmap(fl) & mask == mask
+        let mask = 1u32.wrapping_shl(sl.into());
+        (*self.sl_bitmap(fl)).into() & mask == mask
+    }
+
+    /// Returns whether the free list that blocks of `usable_size` are pushed to is non-empty
+    #[cfg(test)]
+    pub fn is_list_set(&self, usable_size: Size) -> bool {
+        let Indices { fl, sl } = Self::mapping_insert(usable_size);
+        assert!(usize::from(fl) < FLL);
+        self.is_fl_bit_set(fl) && unsafe { self.is_sl_bit_set(fl, sl) }
     }
 
-    pub unsafe fn suitable_sls(&self, fl: u8, sl: u8) -> u16 {
+    pub unsafe fn suitable_sls(&self, fl: u8, sl: u8) -> u32 {
         let sl_bitmap = self.get_sl_bitmap(fl);
-        let mask = (!0u16).wrapping_shl(sl.into());
-        sl_bitmap & mask
+        let mask = (!0u32).wrapping_shl(sl.into());
+        sl_bitmap.into() & mask
     }
 
     /// # Safety
     /// - caller must perform bounds checks
     pub unsafe fn is_sl_empty(&self, fl: u8) -> bool {
-        self.get_sl_bitmap(fl) == 0
+        self.get_sl_bitmap(fl).into() == 0
     }
 
     /// # Safety
     /// - caller must perform bounds checks
-    unsafe fn sl_bitmap(&self, fl: u8) -> &u16 {
+    unsafe fn sl_bitmap(&self, fl: u8) -> &SlBitmap<SL> {
         #[cfg(any(fuzzing, test))]
         debug_assert!(usize::from(fl) < FLL);
 
@@ -122,7 +287,7 @@ impl<const FLL: usize> Header<FLL> {
 
     /// # Safety
     /// - caller must perform bounds checks
-    unsafe fn sl_bitmap_mut(&mut self, fl: u8) -> &mut u16 {
+    unsafe fn sl_bitmap_mut(&mut self, fl: u8) -> &mut SlBitmap<SL> {
         #[cfg(any(fuzzing, test))]
         debug_assert!(usize::from(fl) < FLL);
 
@@ -148,8 +313,11 @@ impl<const FLL: usize> Header<FLL> {
     }
 }
 
-type FirstLevel = [FreeList; consts::SLL as usize];
+#[cfg(feature = "hardening")]
+fn ignore_corruption(_: Corruption) {}
+
 type FreeList = Option<Offset>;
+type SlBitmap<const SL: usize> = <Sl<SL> as Sealed>::Bitmap;
 
 #[cfg(test)]
 mod tests {
@@ -7,8 +7,10 @@ use core::mem::MaybeUninit;
 use core::slice;
 
 #[cfg(not(fuzzing))]
-use crate::block::{Anchor, FreeBlock, Offset};
+use crate::block::{Anchor, FreeBlock, Offset, Pool, UsedBlock};
 use crate::consts;
+#[cfg(not(fuzzing))]
+use crate::Size;
 
 #[cfg(not(fuzzing))]
 pub struct FreeBlocks<'a> {
@@ -23,17 +25,19 @@ pub struct FreeBlocks<'a> {
 impl<'a> FreeBlocks<'a> {
     pub fn new(memory: &'a mut [MaybeUninit<u32>]) -> Self {
         let len = memory.len() * mem::size_of::<u32>();
-        let anchor = Anchor::new(memory);
+        // the anchor reads the pool table through a pointer so the table must outlive it
+        let pools = Box::leak(Box::new([unsafe { Pool::new(memory, 0, len) }]));
+        let anchor = unsafe { Anchor::new(pools) };
         Self {
             anchor,
             prev_phys_block: None,
-            offset: unsafe { Offset::compress(4) },
-            remaining: len - 4,
+            offset: unsafe { Offset::compress(consts::BLOCK_ALIGN.into()) },
+            remaining: len - usize::from(consts::BLOCK_ALIGN),
             yielded_last: false,
         }
     }
 
-    pub fn next(&mut self, usable_size: u16, is_last_phys_block: bool) -> FreeBlock<'a> {
+    pub fn next(&mut self, usable_size: Size, is_last_phys_block: bool) -> FreeBlock<'a> {
         assert!(!self.yielded_last, "already marked a block as the last one");
 
         let block = unsafe {
@@ -68,13 +72,86 @@ impl Drop for FreeBlocks<'_> {
     }
 }
 
+/// Length, in words, of a pool that holds exactly blocks of the given usable sizes
+#[cfg(test)]
+pub const fn pool_len(usable_sizes: &[Size]) -> usize {
+    // the anchor
+    let mut len = consts::BLOCK_ALIGN as usize;
+    let mut i = 0;
+    while i < usable_sizes.len() {
+        len += UsedBlock::HEADER_SIZE as usize + usable_sizes[i] as usize;
+        i += 1;
+    }
+    len / mem::size_of::<u32>()
+}
+
+/// Length, in words, of a pool, added after the first one, that holds exactly blocks of the given
+/// usable sizes
+#[cfg(test)]
+pub const fn added_pool_len(usable_sizes: &[Size]) -> usize {
+    // only the first pool skips `BLOCK_ALIGN` bytes
+    pool_len(usable_sizes) - consts::BLOCK_ALIGN as usize / mem::size_of::<u32>()
+}
+
+/// Usable size of the smallest block that can be allocated: with the `hardening` feature it must
+/// also hold the canary
+#[cfg(test)]
+pub const MIN_ALLOC: Size = {
+    let size = 1 + consts::CANARY_SIZE;
+    let size = if size < consts::MIN_USABLE_SIZE {
+        consts::MIN_USABLE_SIZE
+    } else {
+        size
+    };
+    let align = consts::BLOCK_ALIGN as Size;
+    size.div_ceil(align) * align
+};
+
+/// Memory for a pool that starts on a `BLOCK_ALIGN` boundary with every layout
+#[cfg(test)]
+#[repr(align(16))]
+pub struct Aligned<const N: usize>([MaybeUninit<u32>; N]);
+
+#[cfg(test)]
+impl<const N: usize> Aligned<N> {
+    pub const fn new() -> Self {
+        Self([MaybeUninit::uninit(); N])
+    }
+
+    pub const fn filled(word: u32) -> Self {
+        Self([MaybeUninit::new(word); N])
+    }
+}
+
+#[cfg(test)]
+impl<const N: usize> core::ops::Deref for Aligned<N> {
+    type Target = [MaybeUninit<u32>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl<const N: usize> core::ops::DerefMut for Aligned<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 pub struct Memory {
     ptr: *mut u8,
 }
 
 impl Memory {
+    #[cfg(not(feature = "wide-offsets"))]
+    const SIZE: usize = consts::MAX_POOL_SIZE;
+    // the offset space of `wide-offsets` is too large to allocate; use the default one instead
+    #[cfg(feature = "wide-offsets")]
+    const SIZE: usize = 256 * 1024;
+
     fn layout() -> Layout {
-        Layout::from_size_align(consts::MAX_POOL_SIZE, 64 * 1024).unwrap()
+        Layout::from_size_align(Self::SIZE, 64 * 1024).unwrap()
     }
 
     pub fn new() -> Self {
@@ -84,7 +161,7 @@ impl Memory {
     }
 
     pub fn bytes(&mut self) -> &mut [MaybeUninit<u32>] {
-        unsafe { slice::from_raw_parts_mut(self.ptr.cast(), consts::MAX_POOL_SIZE / 4) }
+        unsafe { slice::from_raw_parts_mut(self.ptr.cast(), Self::SIZE / 4) }
     }
 }
 
@@ -14,37 +14,108 @@ doc = ::embed_doc_image::embed_image!("malloc-histograms", "images/malloc-histog
 //!
 
 #![cfg_attr(not(any(test, fuzzing)), no_std)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
 #![deny(missing_docs)]
 
-use crate::block::Anchor;
+#[cfg(not(feature = "wide-sizes"))]
+use core::num::NonZeroU16;
+#[cfg(feature = "wide-sizes")]
+use core::num::NonZeroU32;
+
 pub use crate::block::Block;
+use crate::block::{Anchor, Pool};
+#[cfg(any(feature = "nightly", feature = "allocator-api2"))]
+pub use crate::cell::TlsfCell;
+pub use crate::error::{AllocError, Corruption, FreeError, PoolError};
 use crate::header::Header;
+pub use crate::ops::{Bin, BinBlocks, Bins, Blocks, CheckedBlocks};
+pub use crate::report::PoolReport;
+pub use crate::sl::{SecondLevel, Sl};
+pub use crate::stats::{Fragmentation, FreeSpan, Stats, Watermarks};
 
 mod block;
+#[cfg(any(feature = "nightly", feature = "allocator-api2"))]
+mod cell;
 mod consts;
+mod error;
+#[cfg(feature = "global")]
+pub mod global;
 mod header;
 #[cfg(any(test, fuzzing))]
 mod helpers;
 mod mapping;
 mod ops;
+mod report;
+mod sl;
+mod stats;
 
 #[cfg(fuzzing)]
 pub use crate::helpers::Memory;
 
+/// The integer type used to track block sizes
+///
+/// `u16` by default; `u32` when the `wide-sizes` feature is enabled
+#[cfg(not(feature = "wide-sizes"))]
+pub type Size = u16;
+/// The integer type used to track block sizes
+///
+/// `u16` by default; `u32` when the `wide-sizes` feature is enabled
+#[cfg(feature = "wide-sizes")]
+pub type Size = u32;
+
+/// The non-zero version of [`Size`]
+#[cfg(not(feature = "wide-sizes"))]
+pub type NonZeroSize = NonZeroU16;
+/// The non-zero version of [`Size`]
+#[cfg(feature = "wide-sizes")]
+pub type NonZeroSize = NonZeroU32;
+
 /// The Two-Level Segregated Fit (TLSF) memory allocator
-pub struct Tlsf<'a, const FLL: usize> {
-    anchor: Option<Anchor<'a>>,
-    header: Header<FLL>,
+///
+/// The allocator can manage up to `POOLS` non-contiguous chunks of memory (see
+/// [`Tlsf::add_pool`]). Each of its `FLL` first level free lists is split into `SL` second level
+/// free lists (see [`Sl`]).
+pub struct Tlsf<'a, const FLL: usize, const POOLS: usize = 1, const SL: usize = 16>
+where
+    Sl<SL>: SecondLevel,
+{
+    pools: [Pool<'a>; POOLS],
+    pool_count: usize,
+    header: Header<FLL, SL>,
+    // per pool: memory at and above this address, except for the block headers, is known to be
+    // zeroed. `usize::MAX` unless the pool was added with `add_pool_zeroed`
+    zeroed_from: [usize; POOLS],
+    watermarks: Watermarks,
 }
 
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Creates a new TLSF allocator with no associated memory
     ///
     /// NOTE: Before you call [`Tlsf::memalign`], you must initialize the allocator with [`Tlsf::initialize`]
     pub const fn empty() -> Self {
         Self {
             header: Header::new(),
-            anchor: None,
+            pools: [Pool::EMPTY; POOLS],
+            pool_count: 0,
+            zeroed_from: [usize::MAX; POOLS],
+            watermarks: Watermarks::ZERO,
+        }
+    }
+
+    /// Returns `None` if no pool has been added to the allocator
+    fn anchor(&self) -> Option<Anchor<'a>> {
+        let pools = self.pools.get(..self.pool_count)?;
+        if pools.is_empty() {
+            None
+        } else {
+            // SAFETY: `pools` is only modified in `add_pool`, which does not hold onto an anchor
+            let anchor = unsafe { Anchor::new(pools) };
+            #[cfg(feature = "safe-linking")]
+            let anchor = anchor.with_secret(self.header.secret());
+            Some(anchor)
         }
     }
 }
@@ -65,13 +136,31 @@ mod tests {
 
     #[test]
     fn stress() {
-        const FLL: usize = 2;
+        stress_with::<2, 16>();
+    }
 
-        let mut tlsf = Tlsf::<{ FLL }>::empty();
+    #[test]
+    fn stress_sl() {
+        stress_with::<4, 4>();
+        stress_with::<2, 32>();
+    }
+
+    fn stress_with<const FLL: usize, const SL: usize>()
+    where
+        Sl<SL>: SecondLevel,
+    {
+        let mut tlsf = Tlsf::<FLL, 1, SL>::empty();
         let mut memory = Memory::new();
         tlsf.initialize(memory.bytes());
 
-        assert_eq!(4, tlsf.free_blocks().len());
+        // with wide sizes, a single block spans the whole pool; otherwise the pool is `BLOCK_ALIGN`
+        // times larger than the largest block
+        let blocks = if cfg!(feature = "wide-sizes") {
+            1
+        } else {
+            usize::from(consts::BLOCK_ALIGN)
+        };
+        assert_eq!(blocks, tlsf.free_blocks().len());
         let total_size_before = tlsf
             .free_blocks()
             .iter()
@@ -86,7 +175,7 @@ mod tests {
         let min_layout = Layout::new::<u8>();
         let mut allocated = 0;
         loop {
-            let size = (rng.next_u32() as usize) % Header::<{ FLL }>::MAX_ALLOC_SIZE as usize;
+            let size = (rng.next_u32() as usize) % Header::<FLL, SL>::MAX_ALLOC_SIZE as usize;
             let align = 1 << (rng.next_u32() as u8 % 6);
             let layout = Layout::from_size_align(size, align).unwrap();
 
@@ -98,7 +187,8 @@ mod tests {
             }
 
             if let Some(alloc) = res {
-                allocated += mem::size_of_val(alloc);
+                // the canary is part of the block but not of the allocation
+                allocated += mem::size_of_val(alloc) + consts::CANARY_SIZE as usize;
 
                 alloc.iter_mut().for_each(|mu| {
                     mu.write(!0);
@@ -111,22 +201,44 @@ mod tests {
             // sanity check that the "statistics" API matches reality
             let mut count = 0;
             let mut used = 0;
+            let mut free = 0;
+            let mut free_count = 0;
             for block in tlsf.blocks() {
                 if block.is_used() {
-                    used += usize::from(block.usable_size());
+                    used += block.usable_size() as usize;
                     count += 1;
+                } else {
+                    free += block.usable_size() as usize;
+                    free_count += 1;
                 }
             }
             assert_eq!(allocs.len(), count);
             assert_eq!(allocated, used);
+
+            let stats = tlsf.stats();
+            assert_eq!(free, stats.free_bytes);
+            assert_eq!(free_count, stats.free_blocks);
+            assert_eq!(used, stats.used_bytes);
+            assert_eq!(count, stats.used_blocks);
+            assert_eq!(Ok(()), tlsf.check());
         }
 
-        assert_eq!(0, tlsf.free_blocks().len());
+        // only blocks too small for the smallest allocation, and its canary, are left
+        assert!(tlsf
+            .free_blocks()
+            .iter()
+            .all(|block| block.usable_size() < helpers::MIN_ALLOC));
 
         allocs.shuffle(&mut rng);
         while let Some(alloc) = allocs.pop() {
             unsafe { tlsf.free(NonNull::from(alloc).cast()) }
         }
+        assert_eq!(Ok(()), tlsf.check());
+
+        let stats = tlsf.stats();
+        assert_eq!(0, stats.used_bytes);
+        assert_eq!(0, stats.used_blocks);
+        assert_eq!(stats.allocs, stats.frees);
 
         let total_size_after = tlsf
             .free_blocks()
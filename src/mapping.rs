@@ -1,17 +1,20 @@
-use crate::consts;
 use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, Size};
 
-impl<const FLL: usize> Header<FLL> {
-    pub fn mapping_insert(size: u16) -> Indices {
-        let (fl, sl) = if size < consts::LOWER_SIZE_THRESHOLD {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    pub fn mapping_insert(size: Size) -> Indices {
+        let (fl, sl) = if size < Self::LOWER_SIZE_THRESHOLD {
             (0, (size >> consts::BLOCK_ALIGN_LOG2) as u8)
         } else if size > Self::UPPER_SIZE_THRESHOLD {
-            (FLL as u8 - 1, consts::SLL - 1)
+            (FLL as u8 - 1, SL as u8 - 1)
         } else {
             let mut fl = find_last_bit_set(size);
-            let sl =
-                size.wrapping_shr(fl.wrapping_sub(consts::SLL_LOG2).into()) as u8 & !consts::SLL;
-            fl = fl.wrapping_sub(consts::MIN_FLL - 1);
+            let sl = size.wrapping_shr(fl.wrapping_sub(Self::SLL_LOG2).into()) as u8 & !(SL as u8);
+            fl = fl.wrapping_sub(Self::MIN_FLL - 1);
             (fl, sl)
         };
 
@@ -19,37 +22,47 @@ impl<const FLL: usize> Header<FLL> {
         debug_assert!(fl < Self::REAL_FLL);
 
         #[cfg(any(fuzzing, test))]
-        debug_assert!(sl < consts::SLL);
+        debug_assert!(usize::from(sl) < SL);
 
         Indices { fl, sl }
     }
 
     /// # Safety
     /// - `size` <= MAX_ALLOC_SIZE
-    pub unsafe fn mapping_search(mut size: u16) -> Indices {
+    pub unsafe fn mapping_search(mut size: Size) -> Indices {
         #[cfg(any(fuzzing, test))]
         debug_assert!(size <= Self::MAX_ALLOC_SIZE);
 
-        if size >= consts::LOWER_SIZE_THRESHOLD {
-            size = (size - 1).wrapping_add(
-                1u16.wrapping_shl(
-                    find_last_bit_set(size)
-                        .wrapping_sub(consts::SLL_LOG2)
-                        .into(),
-                ),
-            );
+        if size >= Self::LOWER_SIZE_THRESHOLD {
+            size = (size - 1).wrapping_add(Size::wrapping_shl(
+                1,
+                find_last_bit_set(size).wrapping_sub(Self::SLL_LOG2).into(),
+            ));
         }
 
         Self::mapping_insert(size)
     }
+
+    /// Returns the smallest block size that maps to the free list at `indices`
+    pub fn mapping_lower_bound(indices: Indices) -> Size {
+        let Indices { fl, sl } = indices;
+        if fl == 0 {
+            Size::from(sl) << consts::BLOCK_ALIGN_LOG2
+        } else {
+            let fl = fl + Self::MIN_FLL - 1;
+            (1 << fl) + (Size::from(sl) << (fl - Self::SLL_LOG2))
+        }
+    }
 }
 
-fn find_last_bit_set(num: u16) -> u8 {
-    15u8.wrapping_sub(num.leading_zeros() as u8)
+// takes both first level (`Size`) and second level (`u16`) bitmaps
+pub fn find_last_bit_set(num: impl Into<u32>) -> u8 {
+    (u32::BITS as u8 - 1).wrapping_sub(num.into().leading_zeros() as u8)
 }
 
-pub fn find_first_bit_set(num: u16) -> u8 {
-    num.trailing_zeros() as u8
+// takes both first level (`Size`) and second level (`u16`) bitmaps
+pub fn find_first_bit_set(num: impl Into<u32>) -> u8 {
+    num.into().trailing_zeros() as u8
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -65,50 +78,228 @@ pub struct Indices {
 mod tests {
     use super::*;
 
+    // the sizes below are those of the default 4-byte block granularity; the mapping scales with it
+    const S: Size = consts::BLOCK_ALIGN as Size / 4;
+
     #[test]
     fn mapping_insert() {
         // 0, 15: 60..64; 1, 0: 64..68
-        assert_eq!(Indices { fl: 0, sl: 15 }, Header::<1>::mapping_insert(60));
-        assert_eq!(Indices { fl: 0, sl: 15 }, Header::<1>::mapping_insert(64));
-        assert_eq!(Indices { fl: 0, sl: 15 }, Header::<1>::mapping_insert(68));
+        assert_eq!(
+            Indices { fl: 0, sl: 15 },
+            Header::<1>::mapping_insert(60 * S)
+        );
+        assert_eq!(
+            Indices { fl: 0, sl: 15 },
+            Header::<1>::mapping_insert(64 * S)
+        );
+        assert_eq!(
+            Indices { fl: 0, sl: 15 },
+            Header::<1>::mapping_insert(68 * S)
+        );
 
-        assert_eq!(Indices { fl: 0, sl: 15 }, Header::<3>::mapping_insert(60));
-        assert_eq!(Indices { fl: 1, sl: 0 }, Header::<3>::mapping_insert(64));
+        assert_eq!(
+            Indices { fl: 0, sl: 15 },
+            Header::<3>::mapping_insert(60 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 0 },
+            Header::<3>::mapping_insert(64 * S)
+        );
 
         // 1, 15: 124..128; 2, 0: 128..136
-        assert_eq!(Indices { fl: 1, sl: 15 }, Header::<3>::mapping_insert(124));
-        assert_eq!(Indices { fl: 2, sl: 0 }, Header::<3>::mapping_insert(128));
-        assert_eq!(Indices { fl: 2, sl: 0 }, Header::<3>::mapping_insert(132));
+        assert_eq!(
+            Indices { fl: 1, sl: 15 },
+            Header::<3>::mapping_insert(124 * S)
+        );
+        assert_eq!(
+            Indices { fl: 2, sl: 0 },
+            Header::<3>::mapping_insert(128 * S)
+        );
+        assert_eq!(
+            Indices { fl: 2, sl: 0 },
+            Header::<3>::mapping_insert(132 * S)
+        );
 
         // UPPER_SIZE_THRESHOLD = 124
-        assert_eq!(Indices { fl: 1, sl: 14 }, Header::<2>::mapping_insert(120));
-        assert_eq!(Indices { fl: 1, sl: 15 }, Header::<2>::mapping_insert(124));
-        assert_eq!(Indices { fl: 1, sl: 15 }, Header::<2>::mapping_insert(128));
-        assert_eq!(Indices { fl: 1, sl: 15 }, Header::<2>::mapping_insert(256));
+        assert_eq!(
+            Indices { fl: 1, sl: 14 },
+            Header::<2>::mapping_insert(120 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 15 },
+            Header::<2>::mapping_insert(124 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 15 },
+            Header::<2>::mapping_insert(128 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 15 },
+            Header::<2>::mapping_insert(256 * S)
+        );
     }
 
     #[test]
     fn mapping_search() {
         unsafe {
             // 0, 15: 60..64; 1, 0: 64..68
-            assert_eq!(Indices { fl: 0, sl: 15 }, Header::<1>::mapping_search(60));
-            assert_eq!(Indices { fl: 0, sl: 15 }, Header::<3>::mapping_search(60));
-            assert_eq!(Indices { fl: 1, sl: 0 }, Header::<3>::mapping_search(64));
+            assert_eq!(
+                Indices { fl: 0, sl: 15 },
+                Header::<1>::mapping_search(60 * S)
+            );
+            assert_eq!(
+                Indices { fl: 0, sl: 15 },
+                Header::<3>::mapping_search(60 * S)
+            );
+            assert_eq!(
+                Indices { fl: 1, sl: 0 },
+                Header::<3>::mapping_search(64 * S)
+            );
 
             // 1, 15: 124..128; 2, 0: 128..136
-            assert_eq!(Indices { fl: 1, sl: 15 }, Header::<3>::mapping_search(124));
-            assert_eq!(Indices { fl: 2, sl: 0 }, Header::<3>::mapping_search(128));
-            assert_eq!(Indices { fl: 2, sl: 1 }, Header::<3>::mapping_search(132));
+            assert_eq!(
+                Indices { fl: 1, sl: 15 },
+                Header::<3>::mapping_search(124 * S)
+            );
+            assert_eq!(
+                Indices { fl: 2, sl: 0 },
+                Header::<3>::mapping_search(128 * S)
+            );
+            assert_eq!(
+                Indices { fl: 2, sl: 1 },
+                Header::<3>::mapping_search(132 * S)
+            );
 
             // 1, 15: 124..128; 2, 0: 128..136
-            assert_eq!(124, Header::<2>::MAX_ALLOC_SIZE);
-            assert_eq!(Indices { fl: 1, sl: 14 }, Header::<2>::mapping_search(120));
-            assert_eq!(Indices { fl: 1, sl: 15 }, Header::<2>::mapping_search(124));
+            assert_eq!(124 * S, Header::<2>::MAX_ALLOC_SIZE);
+            assert_eq!(
+                Indices { fl: 1, sl: 14 },
+                Header::<2>::mapping_search(120 * S)
+            );
+            assert_eq!(
+                Indices { fl: 1, sl: 15 },
+                Header::<2>::mapping_search(124 * S)
+            );
 
             // 2, 14: 240..248; 2, 15: 248..256
-            assert_eq!(248, Header::<3>::MAX_ALLOC_SIZE);
-            assert_eq!(Indices { fl: 2, sl: 15 }, Header::<3>::mapping_search(244));
-            assert_eq!(Indices { fl: 2, sl: 15 }, Header::<3>::mapping_search(248));
+            assert_eq!(248 * S, Header::<3>::MAX_ALLOC_SIZE);
+            assert_eq!(
+                Indices { fl: 2, sl: 15 },
+                Header::<3>::mapping_search(244 * S)
+            );
+            assert_eq!(
+                Indices { fl: 2, sl: 15 },
+                Header::<3>::mapping_search(248 * S)
+            );
         }
     }
+
+    #[test]
+    fn mapping_insert_sl() {
+        // LOWER_SIZE_THRESHOLD = 16
+        assert_eq!(
+            Indices { fl: 0, sl: 3 },
+            Header::<3, 4>::mapping_insert(12 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 0 },
+            Header::<3, 4>::mapping_insert(16 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 1 },
+            Header::<3, 4>::mapping_insert(20 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 3 },
+            Header::<3, 4>::mapping_insert(28 * S)
+        );
+        assert_eq!(
+            Indices { fl: 2, sl: 0 },
+            Header::<3, 4>::mapping_insert(32 * S)
+        );
+
+        // LOWER_SIZE_THRESHOLD = 128
+        assert_eq!(
+            Indices { fl: 0, sl: 31 },
+            Header::<3, 32>::mapping_insert(124 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 0 },
+            Header::<3, 32>::mapping_insert(128 * S)
+        );
+        assert_eq!(
+            Indices { fl: 1, sl: 1 },
+            Header::<3, 32>::mapping_insert(132 * S)
+        );
+        assert_eq!(
+            Indices { fl: 2, sl: 0 },
+            Header::<3, 32>::mapping_insert(256 * S)
+        );
+    }
+
+    #[test]
+    fn mapping_search_sl() {
+        unsafe {
+            // 1, 0: 16..20; 1, 1: 20..24
+            assert_eq!(
+                Indices { fl: 1, sl: 0 },
+                Header::<3, 4>::mapping_search(16 * S)
+            );
+            assert_eq!(
+                Indices { fl: 1, sl: 1 },
+                Header::<3, 4>::mapping_search(20 * S)
+            );
+            // 2, 0: 32..40; 2, 1: 40..48
+            assert_eq!(
+                Indices { fl: 2, sl: 1 },
+                Header::<3, 4>::mapping_search(36 * S)
+            );
+
+            // 2, 0: 256..264; 2, 1: 264..272
+            assert_eq!(
+                Indices { fl: 2, sl: 0 },
+                Header::<3, 32>::mapping_search(256 * S)
+            );
+            assert_eq!(
+                Indices { fl: 2, sl: 1 },
+                Header::<3, 32>::mapping_search(260 * S)
+            );
+        }
+    }
+
+    #[test]
+    fn mapping_lower_bound() {
+        fn check<const FLL: usize, const SL: usize>()
+        where
+            Sl<SL>: SecondLevel,
+        {
+            for fl in 0..FLL as u8 {
+                for sl in 0..SL as u8 {
+                    let indices = Indices { fl, sl };
+                    let size = Header::<FLL, SL>::mapping_lower_bound(indices);
+                    assert_eq!(indices, Header::<FLL, SL>::mapping_insert(size));
+                    if size > 0 {
+                        assert_ne!(indices, Header::<FLL, SL>::mapping_insert(size - 1));
+                    }
+                    if size <= Header::<FLL, SL>::MAX_ALLOC_SIZE {
+                        assert_eq!(indices, unsafe { Header::<FLL, SL>::mapping_search(size) });
+                    }
+                }
+            }
+        }
+
+        // 0, 15: 60..64; 2, 1: 136..144
+        assert_eq!(
+            60 * S,
+            Header::<3>::mapping_lower_bound(Indices { fl: 0, sl: 15 })
+        );
+        assert_eq!(
+            136 * S,
+            Header::<3>::mapping_lower_bound(Indices { fl: 2, sl: 1 })
+        );
+
+        check::<3, 4>();
+        check::<{ consts::MAX_FLL }, 16>();
+        check::<8, 32>();
+    }
 }
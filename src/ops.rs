@@ -1,12 +1,33 @@
+pub use self::bins::{Bin, BinBlocks, Bins};
+pub use self::blocks::Blocks;
+pub use self::checked_blocks::CheckedBlocks;
+#[cfg(feature = "hardening")]
+pub(crate) use self::quarantine::Quarantine;
+
+mod bins;
 mod blocks;
+mod capacity;
+mod check;
+mod checked_blocks;
 mod coalesce;
+mod fragmentation;
 mod free;
+#[cfg(feature = "hardening")]
+mod hardening;
 mod initialize;
 mod malloc;
 mod memalign;
 mod merge;
 mod pop;
 mod push;
+#[cfg(feature = "hardening")]
+mod quarantine;
+mod realloc;
+mod resize;
+#[cfg(feature = "safe-linking")]
+mod safe_linking;
 mod split;
+mod stats;
 mod unlink;
 mod util;
+mod zeroed;
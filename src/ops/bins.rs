@@ -0,0 +1,223 @@
+use core::ops::RangeInclusive;
+
+use crate::block::{Anchor, Offset};
+use crate::header::Header;
+use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
+use crate::{Block, Size, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Returns an iterator over the segregated free lists, or bins, of the allocator
+    ///
+    /// There's one bin per `(fl, sl)` size class, `FLL * SL` bins in total. The iteration order is
+    /// from smallest size class to largest size class
+    ///
+    /// While the iterator is in scope it's not possible to request memory or return memory to the
+    /// allocator
+    pub fn bins(&self) -> Bins<'_, FLL, SL> {
+        Bins {
+            header: &self.header,
+            anchor: self.anchor().unwrap_or(unsafe { Anchor::new(&[]) }),
+            next: Some(Indices { fl: 0, sl: 0 }),
+        }
+    }
+}
+
+/// Iterator over the bins of a [`Tlsf`] allocator, see [`Tlsf::bins`]
+pub struct Bins<'a, const FLL: usize, const SL: usize>
+where
+    Sl<SL>: SecondLevel,
+{
+    header: &'a Header<FLL, SL>,
+    anchor: Anchor<'a>,
+    next: Option<Indices>,
+}
+
+impl<'a, const FLL: usize, const SL: usize> Iterator for Bins<'a, FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    type Item = Bin<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let indices = self.next?;
+        let Indices { fl, sl } = indices;
+
+        self.next = if usize::from(sl) + 1 < SL {
+            Some(Indices { fl, sl: sl + 1 })
+        } else if usize::from(fl) + 1 < FLL {
+            Some(Indices { fl: fl + 1, sl: 0 })
+        } else {
+            None
+        };
+
+        let min_size = Header::<FLL, SL>::mapping_lower_bound(indices);
+        // all the sizes above the largest size class map to it
+        let max_size = self
+            .next
+            .map(|next| Header::<FLL, SL>::mapping_lower_bound(next) - 1)
+            .unwrap_or(Size::MAX);
+
+        // `fl` and `sl` are in bounds
+        let (is_bit_set, head) = unsafe {
+            let sl_bitmap = self.header.get_sl_bitmap(fl).into();
+            (
+                sl_bitmap & (1 << sl) != 0,
+                self.header.get_free_list(fl, sl),
+            )
+        };
+
+        Some(Bin {
+            anchor: self.anchor,
+            fl,
+            sl,
+            sizes: min_size..=max_size,
+            is_bit_set,
+            head,
+        })
+    }
+}
+
+/// A segregated free list of a [`Tlsf`] allocator and its size class
+pub struct Bin<'a> {
+    anchor: Anchor<'a>,
+    fl: u8,
+    sl: u8,
+    sizes: RangeInclusive<Size>,
+    is_bit_set: bool,
+    head: Option<Offset>,
+}
+
+impl<'a> Bin<'a> {
+    /// Returns the first level index of the size class
+    pub fn fl(&self) -> u8 {
+        self.fl
+    }
+
+    /// Returns the second level index of the size class
+    pub fn sl(&self) -> u8 {
+        self.sl
+    }
+
+    /// Returns the range of usable block sizes, in bytes, that map to this size class
+    pub fn sizes(&self) -> RangeInclusive<Size> {
+        self.sizes.clone()
+    }
+
+    /// Returns `true` if the bit of this size class is set in the allocator's bitmaps
+    ///
+    /// The allocator sets the bit when the bin has free blocks
+    pub fn is_bit_set(&self) -> bool {
+        self.is_bit_set
+    }
+
+    /// Returns an iterator over the free blocks linked in this bin
+    ///
+    /// The iteration order is the order in which the allocator hands out the blocks
+    pub fn blocks(&self) -> BinBlocks<'a> {
+        BinBlocks {
+            anchor: self.anchor,
+            next: self.head,
+        }
+    }
+}
+
+/// Iterator over the free blocks of a [`Bin`], see [`Bin::blocks`]
+pub struct BinBlocks<'a> {
+    anchor: Anchor<'a>,
+    next: Option<Offset>,
+}
+
+impl<'a> Iterator for BinBlocks<'a> {
+    type Item = Block<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next?;
+        // the free lists only link blocks covered by `anchor`
+        unsafe {
+            self.next = self.anchor.get_free_block(offset).get_next_free();
+            Some(self.anchor.block_at(offset))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::consts::{self, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::Aligned;
+
+    const GRANULARITY: Size = consts::BLOCK_ALIGN as Size;
+
+    #[test]
+    fn empty() {
+        let tlsf = Tlsf::<2>::empty();
+        assert_eq!(2 * 16, tlsf.bins().count());
+        assert!(tlsf
+            .bins()
+            .all(|bin| !bin.is_bit_set() && bin.blocks().next().is_none()));
+    }
+
+    #[test]
+    fn sizes() {
+        let tlsf = Tlsf::<3, 1, 4>::empty();
+        let bins = tlsf.bins().collect::<Vec<_>>();
+        assert_eq!(3 * 4, bins.len());
+
+        // in units of `GRANULARITY`, 0, 3: 3..4; 1, 0: 4..5; 2, 3: 14..
+        assert_eq!(
+            (0, 3, 3 * GRANULARITY..=4 * GRANULARITY - 1),
+            (bins[3].fl(), bins[3].sl(), bins[3].sizes())
+        );
+        assert_eq!(
+            (1, 0, 4 * GRANULARITY..=5 * GRANULARITY - 1),
+            (bins[4].fl(), bins[4].sl(), bins[4].sizes())
+        );
+        assert_eq!(
+            (2, 3, 14 * GRANULARITY..=Size::MAX),
+            (bins[11].fl(), bins[11].sl(), bins[11].sizes())
+        );
+
+        // the size classes are contiguous
+        for pair in bins.windows(2) {
+            assert_eq!(*pair[0].sizes().end() + 1, *pair[1].sizes().start());
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 8 * GRANULARITY as usize }>::new();
+        tlsf.initialize(&mut memory);
+
+        // leave behind free blocks of three different usable sizes
+        let mut allocs = vec![];
+        for size in [MIN, MIN, MIN + GRANULARITY, MIN, MIN + 2 * GRANULARITY, MIN] {
+            allocs.push(tlsf.malloc(size.try_into().unwrap()).unwrap());
+        }
+        let [a, _, b, _, c, _] = allocs.try_into().unwrap();
+        for alloc in [a, b, c] {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
+
+        let mut linked = 0;
+        for bin in tlsf.bins() {
+            let blocks = bin.blocks().collect::<Vec<_>>();
+            assert_eq!(bin.is_bit_set(), !blocks.is_empty());
+
+            for block in blocks {
+                assert!(block.is_free());
+                assert!(bin.sizes().contains(&block.usable_size()));
+                linked += 1;
+            }
+        }
+
+        assert_eq!(tlsf.stats().free_blocks, linked);
+        assert_eq!(tlsf.free_blocks().len(), linked);
+    }
+}
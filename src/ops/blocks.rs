@@ -1,30 +1,35 @@
 #[cfg(test)]
 use crate::block::FreeBlock;
-use crate::block::{Anchor, Offset};
+use crate::block::{Anchor, Offset, Pool};
 use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
 use crate::{Block, Tlsf};
 
-impl<const FLL: usize> Tlsf<'_, FLL> {
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Returns an iterator over all the memory blocks managed by the allocator
     ///
     /// The iteration order is from lowest memory address to highest memory address
     ///
     /// While the iterator is in scope it's not possible to request memory or return memory to the
     /// allocator
-    pub fn blocks(&self) -> Blocks {
-        if let Some(anchor) = self.anchor {
+    pub fn blocks(&self) -> Blocks<'_> {
+        if let Some(anchor) = self.anchor() {
             self.header.blocks(anchor)
         } else {
             Blocks {
-                anchor: Anchor::new(&mut []),
+                anchor: unsafe { Anchor::new(&[]) },
+                pool: 0,
                 current: None,
             }
         }
     }
 
     #[cfg(test)]
-    pub fn free_blocks(&self) -> Vec<FreeBlock> {
-        let Some(anchor) = self.anchor else {
+    pub(crate) fn free_blocks(&self) -> Vec<FreeBlock<'_>> {
+        let Some(anchor) = self.anchor() else {
             return vec![];
         };
 
@@ -32,11 +37,15 @@ impl<const FLL: usize> Tlsf<'_, FLL> {
     }
 }
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     fn blocks<'a>(&self, anchor: Anchor<'a>) -> Blocks<'a> {
         Blocks {
             anchor,
-            current: Some(unsafe { Offset::compress(4) }),
+            pool: 0,
+            current: anchor.pools().first().map(Pool::first_block),
         }
     }
 
@@ -48,8 +57,11 @@ impl<const FLL: usize> Header<FLL> {
     }
 }
 
+/// Iterator over the memory blocks of a [`Tlsf`] allocator, see [`Tlsf::blocks`]
 pub struct Blocks<'a> {
     anchor: Anchor<'a>,
+    // index of the pool that contains the `current` block
+    pool: usize,
     current: Option<Offset>,
 }
 
@@ -61,7 +73,13 @@ impl<'a> Iterator for Blocks<'a> {
         unsafe {
             let block = self.anchor.block_at(offset);
             let next = self.anchor.next_phys_block(&block);
-            self.current = next.map(|block| self.anchor.offset_of(&block));
+            self.current = if let Some(next) = next {
+                Some(self.anchor.offset_of(&next))
+            } else {
+                // continue with the first block of the next pool
+                self.pool += 1;
+                self.anchor.pools().get(self.pool).map(Pool::first_block)
+            };
             Some(block)
         }
     }
@@ -74,20 +92,27 @@ mod tests {
     use core::ptr::NonNull;
 
     use super::*;
+    use crate::block::UsedBlock;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{added_pool_len, pool_len, Aligned, MIN_ALLOC as MIN};
+    use crate::Size;
+
+    // total size of the smallest block
+    const TOTAL: usize = UsedBlock::HEADER_SIZE as usize + MIN as usize;
 
     #[test]
     fn it_works() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 7];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
         tlsf.initialize(&mut memory);
 
         let blocks = tlsf.blocks().collect::<Vec<_>>();
         assert_eq!(1, blocks.len());
-        assert_eq!(24, blocks[0].total_size());
+        assert_eq!(3 * TOTAL, blocks[0].total_size());
 
         let min_layout = Layout::new::<u8>();
         let first = tlsf.memalign(min_layout).unwrap();
-        assert_eq!(1, first.len());
+        assert_eq!((MIN - CANARY) as usize / 4, first.len());
 
         let blocks = tlsf.blocks().collect::<Vec<_>>();
         assert_eq!(2, blocks.len());
@@ -100,13 +125,13 @@ mod tests {
         });
 
         assert!(!a.is_free());
-        assert_eq!(4, a.usable_size());
+        assert_eq!(MIN, a.usable_size());
 
         assert!(b.is_free());
-        assert_eq!(16, b.total_size());
+        assert_eq!(2 * TOTAL, b.total_size());
 
         let second = tlsf.memalign(min_layout).unwrap();
-        assert_eq!(1, second.len());
+        assert_eq!((MIN - CANARY) as usize / 4, second.len());
 
         let blocks = tlsf.blocks().collect::<Vec<_>>();
         assert_eq!(3, blocks.len());
@@ -120,13 +145,13 @@ mod tests {
         });
 
         assert!(!a.is_free());
-        assert_eq!(4, a.usable_size());
+        assert_eq!(MIN, a.usable_size());
 
         assert!(!b.is_free());
-        assert_eq!(4, b.usable_size());
+        assert_eq!(MIN, b.usable_size());
 
         assert!(c.is_free());
-        assert_eq!(8, c.total_size());
+        assert_eq!(TOTAL, c.total_size());
 
         unsafe { tlsf.free(NonNull::from(first).cast()) }
 
@@ -139,13 +164,13 @@ mod tests {
         });
 
         assert!(a.is_free());
-        assert_eq!(4, a.usable_size());
+        assert_eq!(MIN, a.usable_size());
 
         assert!(!b.is_free());
-        assert_eq!(4, b.usable_size());
+        assert_eq!(MIN, b.usable_size());
 
         assert!(c.is_free());
-        assert_eq!(8, c.total_size());
+        assert_eq!(TOTAL, c.total_size());
     }
 
     #[test]
@@ -156,4 +181,39 @@ mod tests {
 
         assert!(tlsf.blocks().next().is_none());
     }
+
+    #[test]
+    fn layout() {
+        let mut tlsf = Tlsf::<2, 2>::empty();
+        const SIZE: Size = 10 * consts::BLOCK_ALIGN as Size;
+        let mut first_pool = Aligned::<{ pool_len(&[SIZE, MIN]) }>::new();
+        let mut second_pool =
+            Aligned::<{ added_pool_len(&[SIZE - 2 * consts::BLOCK_ALIGN as Size]) }>::new();
+        tlsf.initialize(&mut first_pool);
+        tlsf.add_pool(&mut second_pool);
+
+        // doesn't fit in the second pool
+        let alloc = tlsf.malloc((SIZE - CANARY).try_into().unwrap()).unwrap();
+        let addr = alloc.as_ptr() as usize;
+
+        let blocks = tlsf.blocks().collect::<Vec<_>>();
+        let [a, b, c] = blocks.try_into().unwrap();
+
+        assert!(a.is_used());
+        assert_eq!(addr, a.body_addr());
+        assert_eq!(None, a.prev_phys_block());
+        assert!(!a.is_last_phys_block());
+
+        // physically adjacent to `a`
+        assert_eq!(Some(a.offset()), b.prev_phys_block());
+        let align = usize::from(consts::BLOCK_ALIGN);
+        assert_eq!(a.offset() * align + a.total_size(), b.offset() * align);
+        assert_eq!(a.body_addr() + a.total_size(), b.body_addr());
+        assert!(b.is_last_phys_block());
+
+        // first block of the second pool
+        assert_eq!(None, c.prev_phys_block());
+        assert!(c.offset() > b.offset());
+        assert!(c.is_last_phys_block());
+    }
 }
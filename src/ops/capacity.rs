@@ -0,0 +1,168 @@
+use core::alloc::Layout;
+
+use crate::header::Header;
+use crate::mapping::{self, Indices};
+use crate::ops::{memalign, util};
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, Size, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Returns a lower bound on the size of the largest block that can be allocated right now
+    ///
+    /// A [`Tlsf::malloc`] request of up to this many bytes is guaranteed to succeed. The bound is
+    /// the smallest size of the largest non-empty size class so larger requests may succeed as
+    /// well. Returns `0` when there's no free memory.
+    ///
+    /// With the `hardening` feature, blocks held in the quarantine are not accounted for. An
+    /// allocation drains the quarantine when no free block fits it so the actual bound may be
+    /// larger.
+    ///
+    /// This operation executes in constant time (`O(1)`)
+    pub fn largest_free_class(&self) -> Size {
+        self.header.largest_free_class()
+    }
+
+    /// Returns whether a [`Tlsf::memalign`] request with the given `layout` would succeed right now
+    ///
+    /// With the `hardening` feature, blocks held in the quarantine are not accounted for. The request
+    /// may still succeed when this returns `false` because it drains the quarantine when no free
+    /// block fits it.
+    ///
+    /// This operation executes in constant time (`O(1)`) and does not modify the allocator
+    pub fn can_allocate(&self, layout: Layout) -> bool {
+        if self.anchor().is_none() || layout.size() == 0 {
+            return false;
+        }
+
+        let (Ok(size), Ok(align)) = (layout.size().try_into(), layout.align().try_into()) else {
+            return false;
+        };
+
+        util::round_up_block_size(size)
+            .and_then(|size| memalign::worst_case_size(size, align))
+            .is_some_and(|size| self.header.can_pop(size))
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    fn largest_free_class(&self) -> Size {
+        let fl_bitmap = self.suitable_fls(0);
+        if fl_bitmap == 0 {
+            return 0;
+        }
+
+        let fl = mapping::find_last_bit_set(fl_bitmap);
+        // a set `fl` bit implies a non-zero `sl` bitmap
+        let sl = mapping::find_last_bit_set(unsafe { self.get_sl_bitmap(fl) });
+        // no free block is smaller than `MIN_USABLE_SIZE`, whatever the bound of its size class
+        let size = Self::mapping_lower_bound(Indices { fl, sl })
+            .max(consts::MIN_USABLE_SIZE)
+            .min(Self::MAX_ALLOC_SIZE);
+        // the canary takes the last word of the block, see `util::round_up_block_size`
+        size.saturating_sub(consts::CANARY_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::helpers::{pool_len, Aligned};
+
+    #[test]
+    fn empty() {
+        let tlsf = Tlsf::<2>::empty();
+        assert_eq!(0, tlsf.largest_free_class());
+        assert!(!tlsf.can_allocate(Layout::new::<u32>()));
+    }
+
+    #[test]
+    fn it_works() {
+        let mut tlsf = Tlsf::<2>::empty();
+        // the smallest size of size class 0, 14
+        const SIZE: Size = 14 * consts::BLOCK_ALIGN as Size;
+        let mut memory = Aligned::<{ pool_len(&[SIZE]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        // a single free block that fills size class 0, 14, minus the canary that it must hold
+        const LARGEST: Size = SIZE - consts::CANARY_SIZE;
+        let layout = |size| Layout::from_size_align(size as usize, 1).unwrap();
+        assert_eq!(LARGEST, tlsf.largest_free_class());
+        assert!(tlsf.can_allocate(layout(LARGEST)));
+        assert!(!tlsf.can_allocate(layout(LARGEST + Size::from(consts::BLOCK_ALIGN))));
+        assert!(!tlsf.can_allocate(layout(0)));
+        assert!(!tlsf.can_allocate(Layout::from_size_align(4, 1 << 16).unwrap()));
+
+        let stats = tlsf.stats();
+        let alloc = tlsf.malloc(LARGEST.try_into().unwrap()).unwrap();
+        assert_eq!(0, tlsf.largest_free_class());
+        assert!(!tlsf.can_allocate(Layout::new::<u32>()));
+
+        // the queries don't modify the allocator
+        unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        assert_eq!(LARGEST, tlsf.largest_free_class());
+        assert_eq!(stats.free_bytes, tlsf.stats().free_bytes);
+    }
+
+    #[test]
+    fn smallest_class() {
+        let mut tlsf = Tlsf::<2>::empty();
+        // a single free block of the smallest size
+        let mut memory = Aligned::<{ pool_len(&[consts::MIN_USABLE_SIZE]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let largest = consts::MIN_USABLE_SIZE - consts::CANARY_SIZE;
+        assert_eq!(largest, tlsf.largest_free_class());
+        if largest != 0 {
+            assert!(tlsf.can_allocate(Layout::from_size_align(largest as usize, 1).unwrap()));
+            assert!(tlsf.malloc(largest.try_into().unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn matches_memalign() {
+        let mut tlsf = Tlsf::<3>::empty();
+        let mut memory = [MaybeUninit::uninit(); 512];
+        tlsf.initialize(&mut memory);
+
+        // fill the memory with allocations of varying size, up to the point of OOM
+        let mut allocs = vec![];
+        for i in 0.. {
+            let largest = tlsf.largest_free_class();
+            if largest != 0 {
+                let alloc = tlsf.malloc(largest.try_into().unwrap());
+                assert!(alloc.is_some());
+                unsafe { tlsf.free(NonNull::from(alloc.unwrap()).cast()) }
+            }
+
+            for size in (4..=Header::<3>::MAX_ALLOC_SIZE as usize + 4).step_by(4) {
+                for align in [4, 8, 16, 32] {
+                    let layout = Layout::from_size_align(size, align).unwrap();
+                    let expected = tlsf.can_allocate(layout);
+                    let alloc = tlsf.memalign(layout);
+                    assert_eq!(expected, alloc.is_some(), "{layout:?}");
+                    if let Some(alloc) = alloc {
+                        unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+                    }
+                }
+            }
+
+            let size = 4 * (i % 16) + 4;
+            match tlsf.malloc(size.try_into().unwrap()) {
+                Some(alloc) => allocs.push(alloc),
+                None => {
+                    assert!(tlsf.largest_free_class() < size);
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use crate::block::{Anchor, FreeBlock, Offset, UsedBlock};
+use crate::header::Header;
+use crate::mapping::{self, Indices};
+use crate::sl::{SecondLevel, Sl};
+use crate::{CheckedBlocks, Corruption, Size, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Verifies the integrity of the heap
+    ///
+    /// This walks all the memory blocks and all the free lists, and checks that the block headers,
+    /// the free lists and the bitmaps agree with each other. Corrupted block headers are detected
+    /// without following their links outside the pools.
+    ///
+    /// This operation executes in linear time (`O(n)`) when the heap is intact
+    pub fn check(&self) -> Result<(), Corruption> {
+        let anchor = self.anchor().unwrap_or(unsafe { Anchor::new(&[]) });
+        unsafe { self.header.check(anchor) }
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// # Safety
+    /// - `header` must be associated to the given `anchor`
+    unsafe fn check(&self, anchor: Anchor<'_>) -> Result<(), Corruption> {
+        let free_blocks = self.check_blocks(anchor)?;
+        let linked_blocks = self.check_free_lists(anchor, free_blocks)?;
+        if linked_blocks != free_blocks {
+            // some free blocks link to each other but can't be reached from the free lists
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(check_unreachable_free_block);
+
+            let block = self.find_unreachable_free_block(anchor, free_blocks);
+            return Err(Corruption::FreeList {
+                block: block.map(|offset| offset.get()).unwrap_or(0),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Walks the physical blocks of all pools and returns the number of free blocks
+    unsafe fn check_blocks(&self, anchor: Anchor<'_>) -> Result<usize, Corruption> {
+        let mut free_blocks = 0;
+        // usable size of the previous block, if it's free
+        let mut prev_free_size = None;
+        for block in CheckedBlocks::new(anchor) {
+            let block = block?;
+            if block.prev_phys_block().is_none() {
+                // first block of a pool
+                prev_free_size = None;
+            }
+
+            if block.is_free() {
+                // the allocator merges adjacent free blocks unless the result would be too large
+                let could_merge = prev_free_size.is_some_and(|prev_size: Size| {
+                    Size::try_from((prev_size as usize).wrapping_add(block.total_size())).is_ok()
+                });
+                if could_merge {
+                    return Err(Corruption::Uncoalesced {
+                        block: block.offset(),
+                    });
+                }
+
+                self.check_free_block(anchor, block.raw_offset())?;
+                free_blocks += 1;
+            }
+
+            prev_free_size = block.is_free().then(|| block.usable_size());
+        }
+
+        Ok(free_blocks)
+    }
+
+    /// Checks the free list links of the free block at `offset`
+    unsafe fn check_free_block(
+        &self,
+        anchor: Anchor<'_>,
+        offset: Offset,
+    ) -> Result<(), Corruption> {
+        let block = anchor.get_free_block(offset);
+        let indices = Self::mapping_insert(block.usable_size());
+
+        match block.get_prev_free() {
+            None => {
+                if self.get_free_list(indices.fl, indices.sl) != Some(offset) {
+                    return Err(Corruption::FreeList {
+                        block: offset.get(),
+                    });
+                }
+            }
+
+            Some(prev) => {
+                let Some(prev) = Self::linked_free_block(anchor, prev) else {
+                    return Err(Corruption::FreeLink {
+                        block: offset.get(),
+                    });
+                };
+
+                if prev.get_next_free() != Some(offset) {
+                    return Err(Corruption::FreeLink {
+                        block: offset.get(),
+                    });
+                }
+
+                if Self::mapping_insert(prev.usable_size()) != indices {
+                    return Err(Corruption::FreeList {
+                        block: offset.get(),
+                    });
+                }
+            }
+        }
+
+        if let Some(next) = block.get_next_free() {
+            let is_symmetric = Self::linked_free_block(anchor, next)
+                .is_some_and(|next| next.get_prev_free() == Some(offset));
+            if !is_symmetric {
+                return Err(Corruption::FreeLink {
+                    block: offset.get(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the bitmaps and returns the number of blocks linked in the free lists
+    ///
+    /// Gives up on a list once more than `free_blocks` blocks have been visited, e.g. due to a
+    /// cycle
+    unsafe fn check_free_lists(
+        &self,
+        anchor: Anchor<'_>,
+        free_blocks: usize,
+    ) -> Result<usize, Corruption> {
+        let fl_bitmap = self.suitable_fls(0);
+        if let Some(fl) = (fl_bitmap != 0)
+            .then(|| mapping::find_last_bit_set(fl_bitmap))
+            .filter(|fl| usize::from(*fl) >= FLL)
+        {
+            return Err(Corruption::Bitmap { fl, sl: 0 });
+        }
+
+        let mut linked_blocks = 0;
+        for fl in 0..FLL as u8 {
+            let sl_bitmap: u32 = self.get_sl_bitmap(fl).into();
+            let is_fl_bit_set = fl_bitmap & Size::wrapping_shl(1, fl.into()) != 0;
+            if is_fl_bit_set != (sl_bitmap != 0) {
+                return Err(Corruption::Bitmap { fl, sl: 0 });
+            }
+
+            if let Some(sl) = (sl_bitmap != 0)
+                .then(|| mapping::find_last_bit_set(sl_bitmap))
+                .filter(|sl| usize::from(*sl) >= SL)
+            {
+                return Err(Corruption::Bitmap { fl, sl });
+            }
+
+            for sl in 0..SL as u8 {
+                let is_sl_bit_set = sl_bitmap & (1 << sl) != 0;
+                let mut next = self.get_free_list(fl, sl);
+                if is_sl_bit_set != next.is_some() {
+                    return Err(Corruption::Bitmap { fl, sl });
+                }
+
+                let mut prev: Option<Offset> = None;
+                while let Some(offset) = next {
+                    let Some(block) = Self::linked_free_block(anchor, offset) else {
+                        return Err(match prev {
+                            Some(prev) => Corruption::FreeLink { block: prev.get() },
+                            // the head is out of the pools or not free
+                            None => Corruption::Bitmap { fl, sl },
+                        });
+                    };
+
+                    if block.get_prev_free() != prev
+                        || Self::mapping_insert(block.usable_size()) != (Indices { fl, sl })
+                    {
+                        return Err(Corruption::FreeList {
+                            block: offset.get(),
+                        });
+                    }
+
+                    linked_blocks += 1;
+                    if linked_blocks > free_blocks {
+                        return Err(Corruption::FreeLink {
+                            block: offset.get(),
+                        });
+                    }
+
+                    prev = Some(offset);
+                    next = block.get_next_free();
+                }
+            }
+        }
+
+        Ok(linked_blocks)
+    }
+
+    /// Returns the first free block whose `prev_free` links do not lead to the head of a free list
+    unsafe fn find_unreachable_free_block(
+        &self,
+        anchor: Anchor<'_>,
+        free_blocks: usize,
+    ) -> Option<Offset> {
+        for pool in anchor.pools() {
+            let mut offset = pool.first_block();
+            loop {
+                let block = anchor.block_at(offset);
+                if block.is_free() && !self.reaches_head(anchor, offset, free_blocks) {
+                    return Some(offset);
+                }
+
+                if block.is_last_phys_block() {
+                    break;
+                }
+
+                offset = offset.add(block.total_size());
+            }
+        }
+
+        None
+    }
+
+    /// Returns `true` if following the `prev_free` links of the free block at `offset` leads to the
+    /// head of a free list in at most `free_blocks` steps
+    unsafe fn reaches_head(&self, anchor: Anchor<'_>, offset: Offset, free_blocks: usize) -> bool {
+        let mut current = offset;
+        for _ in 0..=free_blocks {
+            let Some(block) = Self::linked_free_block(anchor, current) else {
+                return false;
+            };
+
+            match block.get_prev_free() {
+                Some(prev) => current = prev,
+                None => {
+                    let Indices { fl, sl } = Self::mapping_insert(block.usable_size());
+                    return self.get_free_list(fl, sl) == Some(current);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Returns the free block at `offset` if `offset` is covered by a pool and the block is free
+    pub(super) unsafe fn linked_free_block<'a>(
+        anchor: Anchor<'a>,
+        offset: Offset,
+    ) -> Option<FreeBlock<'a>> {
+        let header_size = usize::from(FreeBlock::HEADER_SIZE).max(UsedBlock::HEADER_SIZE.into());
+        if anchor.covers(offset, header_size) && anchor.block_at(offset).is_free() {
+            Some(anchor.get_free_block(offset))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::block::UsedBlock;
+    use crate::consts::{CANARY_SIZE, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::{pool_len, Aligned, FreeBlocks, MIN_ALLOC};
+    use crate::Size;
+
+    #[test]
+    fn intact() {
+        let mut tlsf = Tlsf::<2>::empty();
+        assert_eq!(Ok(()), tlsf.check());
+
+        let mut memory = [MaybeUninit::uninit(); 64];
+        tlsf.initialize(&mut memory);
+        assert_eq!(Ok(()), tlsf.check());
+
+        let mut allocs = vec![];
+        for size in [4, 8, 12, 16, 4, 8] {
+            allocs.push(tlsf.malloc(size.try_into().unwrap()).unwrap());
+        }
+        for alloc in allocs.into_iter().step_by(2) {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+            assert_eq!(Ok(()), tlsf.check());
+        }
+    }
+
+    #[test]
+    fn block_size() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = [MaybeUninit::uninit(); 16];
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc(4.try_into().unwrap()).unwrap();
+        let block = tlsf.blocks().next().unwrap().offset();
+
+        // buffer underflow into the size field, at the start of the header of the block
+        unsafe {
+            let header = alloc
+                .as_mut_ptr()
+                .cast::<u8>()
+                .sub(UsedBlock::HEADER_SIZE.into());
+            header.cast::<Size>().write(0x1000);
+        }
+        assert_eq!(Err(Corruption::BlockSize { block }), tlsf.check());
+    }
+
+    #[test]
+    fn last_phys_block() {
+        let mut header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN, 3 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let second = free_blocks.next(3 * MIN, true);
+            header.push(anchor, second);
+            first.set_last_phys_block();
+            let block = anchor.offset_of(&first).get();
+            first.into_used(anchor);
+
+            assert_eq!(
+                Err(Corruption::LastPhysBlock { block }),
+                header.check(anchor)
+            );
+        }
+    }
+
+    #[test]
+    fn prev_phys_block() {
+        let mut header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN, 3 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let second = free_blocks.next(3 * MIN, true);
+            let offset = anchor.offset_of(&second);
+            header.push(anchor, second);
+            anchor.block_at(offset).set_prev_phys_block(offset);
+            first.into_used(anchor);
+
+            let block = offset.get();
+            assert_eq!(
+                Err(Corruption::PrevPhysBlock { block }),
+                header.check(anchor)
+            );
+        }
+    }
+
+    #[test]
+    fn uncoalesced() {
+        let mut header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN, 3 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let second = free_blocks.next(3 * MIN, true);
+            let block = anchor.offset_of(&second).get();
+            header.push(anchor, first);
+            header.push(anchor, second);
+
+            assert_eq!(Err(Corruption::Uncoalesced { block }), header.check(anchor));
+        }
+    }
+
+    #[test]
+    fn uncoalesced_after_corruption() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN_ALLOC; 3]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (MIN_ALLOC - CANARY_SIZE).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        let second = tlsf.malloc(size).unwrap();
+        let _third = tlsf.malloc(size).unwrap();
+        unsafe { tlsf.free(NonNull::from(first).cast()) }
+        assert_eq!(Ok(()), tlsf.check());
+
+        // flag `second` as free, as a stray write into its header could
+        let block = tlsf.blocks().nth(1).unwrap().offset();
+        unsafe {
+            let anchor = tlsf.anchor().unwrap();
+            let second = anchor.get_used_block(NonNull::from(second).cast()).unwrap();
+            second.into_free(anchor);
+        }
+        assert_eq!(Err(Corruption::Uncoalesced { block }), tlsf.check());
+    }
+
+    #[test]
+    fn free_list() {
+        let header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN, 3 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            // never linked into a free list
+            let second = free_blocks.next(3 * MIN, true);
+            first.into_used(anchor);
+
+            let block = anchor.offset_of(&second).get();
+            assert_eq!(Err(Corruption::FreeList { block }), header.check(anchor));
+        }
+    }
+
+    #[test]
+    fn free_link() {
+        let mut header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 4]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let second = free_blocks.next(MIN, false);
+            let third = free_blocks.next(MIN, false);
+            let fourth = free_blocks.next(MIN, true);
+            let used = anchor.offset_of(&second);
+            second.into_used(anchor);
+            fourth.into_used(anchor);
+            header.push(anchor, first);
+            header.push(anchor, third);
+
+            // `first` is the tail of the list; make it link back to a used block
+            let [_, tail] = header.linked_free_blocks(anchor).try_into().unwrap();
+            let block = anchor.offset_of(&tail).get();
+            tail.set_prev_free(used);
+
+            assert_eq!(Err(Corruption::FreeLink { block }), header.check(anchor));
+        }
+    }
+
+    #[test]
+    fn bitmap() {
+        let mut header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[2 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let block = free_blocks.next(2 * MIN, true);
+            header.push(anchor, block);
+            assert_eq!(Ok(()), header.check(anchor));
+
+            let Indices { fl, sl } = Header::<2>::mapping_insert(2 * MIN);
+            header.clear_sl_bit(fl, sl);
+            assert_eq!(Err(Corruption::Bitmap { fl, sl: 0 }), header.check(anchor));
+
+            header.clear_fl_bit(fl);
+            assert_eq!(Err(Corruption::Bitmap { fl, sl }), header.check(anchor));
+        }
+    }
+
+    #[test]
+    fn unreachable() {
+        let header = Header::<2>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let second = free_blocks.next(MIN, false);
+            let third = free_blocks.next(MIN, true);
+            second.into_used(anchor);
+
+            // `first` and `third` link to each other but aren't in any free list
+            let a = anchor.offset_of(&first);
+            let b = anchor.offset_of(&third);
+            first.set_next_free(b);
+            first.set_prev_free(b);
+            third.set_next_free(a);
+            third.set_prev_free(a);
+
+            let res = {
+                #[cfg(not(miri))]
+                cov_mark::check!(check_unreachable_free_block);
+
+                header.check(anchor)
+            };
+            assert_eq!(Err(Corruption::FreeList { block: a.get() }), res);
+        }
+    }
+}
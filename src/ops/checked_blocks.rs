@@ -0,0 +1,195 @@
+use crate::block::{Anchor, FreeBlock, Offset, Pool};
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, Block, Corruption, Size, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Returns an iterator over all the memory blocks managed by the allocator that validates
+    /// each block header before trusting it
+    ///
+    /// Unlike [`Tlsf::blocks`], this iterator never reads memory outside the pools, even when
+    /// block headers have been corrupted. The iterator yields an error, and then stops, when a
+    /// header has an invalid size, an invalid last block flag or an invalid `prev_phys_block`
+    /// link. The free list links of free blocks are not validated; see [`Tlsf::check`] for that.
+    ///
+    /// The iteration order is from lowest memory address to highest memory address
+    pub fn checked_blocks(&self) -> CheckedBlocks<'_> {
+        CheckedBlocks::new(self.anchor().unwrap_or(unsafe { Anchor::new(&[]) }))
+    }
+}
+
+/// Iterator over the memory blocks of a [`Tlsf`] allocator, see [`Tlsf::checked_blocks`]
+pub struct CheckedBlocks<'a> {
+    anchor: Anchor<'a>,
+    // index of the pool that contains the `current` block
+    pool: usize,
+    current: Option<Offset>,
+    // block that physically precedes the `current` block
+    prev: Option<Offset>,
+}
+
+impl<'a> CheckedBlocks<'a> {
+    pub(crate) fn new(anchor: Anchor<'a>) -> Self {
+        Self {
+            anchor,
+            pool: 0,
+            current: anchor.pools().first().map(Pool::first_block),
+            prev: None,
+        }
+    }
+
+    /// Validates `block` and returns the offset of the next block in `pool`
+    fn validate(&self, pool: &Pool<'a>, block: &Block<'a>) -> Result<Option<Offset>, Corruption> {
+        let free_header_size = usize::from(FreeBlock::HEADER_SIZE);
+        let id = block.offset();
+
+        let end = (id << consts::BLOCK_ALIGN_LOG2).wrapping_add(block.total_size());
+        if block.usable_size() & (Size::from(consts::BLOCK_ALIGN) - 1) != 0
+            || end > pool.end()
+            || (block.is_free() && block.total_size() < free_header_size)
+        {
+            return Err(Corruption::BlockSize { block: id });
+        }
+
+        if block.prev_phys_block() != self.prev.map(|prev| prev.get()) {
+            return Err(Corruption::PrevPhysBlock { block: id });
+        }
+
+        // the memory past the last block is too small to hold a block
+        let has_room = pool.end() - end >= free_header_size;
+        if block.is_last_phys_block() == has_room {
+            return Err(Corruption::LastPhysBlock { block: id });
+        }
+
+        // `end` is within the pool, as checked above
+        Ok(has_room.then(|| unsafe { Offset::compress(end) }))
+    }
+}
+
+impl<'a> Iterator for CheckedBlocks<'a> {
+    type Item = Result<Block<'a>, Corruption>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.current?;
+        let pool = *self.anchor.pools().get(self.pool)?;
+
+        // `offset` is the first block of `pool` or was validated to fit in `pool`
+        let block = unsafe { self.anchor.block_at(offset) };
+        match self.validate(&pool, &block) {
+            Ok(Some(next)) => {
+                self.prev = Some(offset);
+                self.current = Some(next);
+            }
+
+            Ok(None) => {
+                // continue with the first block of the next pool
+                self.pool += 1;
+                self.prev = None;
+                self.current = self.anchor.pools().get(self.pool).map(Pool::first_block);
+            }
+
+            Err(corruption) => {
+                self.current = None;
+                return Some(Err(corruption));
+            }
+        }
+
+        Some(Ok(block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::helpers::Aligned;
+
+    const GRANULARITY: Size = consts::BLOCK_ALIGN as Size;
+    // the canary of the `hardening` feature sits between an allocation and the next block
+    const CANARY_WORDS: usize = consts::CANARY_SIZE as usize / 4;
+
+    #[test]
+    fn matches_blocks() {
+        let mut tlsf = Tlsf::<2, 2>::empty();
+        assert!(tlsf.checked_blocks().next().is_none());
+
+        let mut first_pool = Aligned::<{ 8 * GRANULARITY as usize }>::new();
+        let mut second_pool = Aligned::<{ 2 * GRANULARITY as usize }>::new();
+        tlsf.initialize(&mut first_pool);
+        tlsf.add_pool(&mut second_pool);
+
+        let mut allocs = vec![];
+        for size in [1, 2, 3, 4].map(|n| n * GRANULARITY) {
+            allocs.push(tlsf.malloc(size.try_into().unwrap()).unwrap());
+        }
+        unsafe { tlsf.free(NonNull::from(allocs.swap_remove(1)).cast()) }
+
+        let blocks = tlsf
+            .blocks()
+            .map(|block| block.offset())
+            .collect::<Vec<_>>();
+        let checked = tlsf
+            .checked_blocks()
+            .map(|block| block.map(|block| block.offset()))
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(Ok(blocks), checked);
+    }
+
+    #[test]
+    fn corrupted_size() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 4 * GRANULARITY as usize }>::new();
+        tlsf.initialize(&mut memory);
+
+        let first = tlsf.malloc(GRANULARITY.try_into().unwrap()).unwrap();
+        let block = tlsf.blocks().nth(1).unwrap().offset();
+
+        // buffer overflow, past the canary, into the header of the next block; the size is too
+        // large for the pool
+        unsafe {
+            first
+                .as_mut_ptr()
+                .add(first.len() + CANARY_WORDS)
+                .cast::<Size>()
+                .write(0xfff0)
+        }
+
+        let mut blocks = tlsf.checked_blocks();
+        assert!(blocks.next().unwrap().is_ok());
+        assert_eq!(
+            Some(Err(Corruption::BlockSize { block })),
+            blocks.next().map(|block| block.map(|block| block.offset()))
+        );
+        assert!(blocks.next().is_none());
+    }
+
+    #[test]
+    fn corrupted_flags() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 4 * GRANULARITY as usize }>::new();
+        tlsf.initialize(&mut memory);
+
+        let first = tlsf.malloc(GRANULARITY.try_into().unwrap()).unwrap();
+        let next = tlsf.blocks().nth(1).unwrap();
+        let (block, size) = (next.offset(), next.usable_size());
+        assert!(next.is_free() && next.is_last_phys_block());
+
+        // clears the "last block" flag of the next block and keeps its size and "free" flag
+        unsafe {
+            first
+                .as_mut_ptr()
+                .add(first.len() + CANARY_WORDS)
+                .cast::<Size>()
+                .write(size | 1)
+        }
+
+        let res = tlsf
+            .checked_blocks()
+            .map(|block| block.map(|block| block.offset()))
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(Err(Corruption::LastPhysBlock { block }), res);
+    }
+}
@@ -1,19 +1,24 @@
 use crate::block::{Anchor, FreeBlock};
 use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::Size;
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     pub(super) unsafe fn coalesce<'a>(&mut self, anchor: Anchor<'a>, mut block: FreeBlock<'a>) {
         let (prev, next) = self.merge_candidates(anchor, &block);
 
         if let Some(prev) = prev {
             self.unlink(anchor, &prev);
-            prev.merge(anchor, block);
+            prev.merge(anchor, &block);
             block = prev;
         }
 
         if let Some(next) = next {
             self.unlink(anchor, &next);
-            block.merge(anchor, next);
+            block.merge(anchor, &next);
         }
 
         self.push(anchor, block);
@@ -24,7 +29,7 @@ impl<const FLL: usize> Header<FLL> {
         anchor: Anchor<'a>,
         block: &FreeBlock<'a>,
     ) -> (Option<FreeBlock<'a>>, Option<FreeBlock<'a>>) {
-        let size = usize::from(block.usable_size());
+        let size = block.usable_size() as usize;
         let prev = anchor
             .prev_phys_block(block)
             .and_then(|block| block.try_into_free(anchor));
@@ -37,38 +42,37 @@ impl<const FLL: usize> Header<FLL> {
                 let prev_size = prev.total_size();
                 let next_size = next.total_size();
 
-                if u16::try_from(size.wrapping_add(prev_size).wrapping_add(next_size)).is_ok() {
+                if Size::try_from(size.wrapping_add(prev_size).wrapping_add(next_size)).is_ok() {
                     #[cfg(all(test, not(miri)))]
                     cov_mark::hit!(merge_both_sides);
 
                     return (Some(prev), Some(next));
-                } else if prev_size > next_size
-                    && u16::try_from(size.wrapping_add(prev_size)).is_ok()
-                {
+                }
+
+                // merging with one neighbor makes the result too large to merge with the other so
+                // prefer the larger one
+                let fits = |other: usize| Size::try_from(size.wrapping_add(other)).is_ok();
+                if prev_size >= next_size && fits(prev_size) {
                     return (Some(prev), None);
-                } else if next_size > prev_size
-                    && u16::try_from(size.wrapping_add(next_size)).is_ok()
-                {
+                } else if fits(next_size) {
                     return (None, Some(next));
+                } else if fits(prev_size) {
+                    return (Some(prev), None);
                 }
             }
 
-            (Some(prev), _) => {
-                if u16::try_from(size.wrapping_add(prev.total_size())).is_ok() {
-                    #[cfg(all(test, not(miri)))]
-                    cov_mark::hit!(merge_prev);
+            (Some(prev), _) if Size::try_from(size.wrapping_add(prev.total_size())).is_ok() => {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(merge_prev);
 
-                    return (Some(prev), None);
-                }
+                return (Some(prev), None);
             }
 
-            (_, Some(next)) => {
-                if u16::try_from(size.wrapping_add(next.total_size())).is_ok() {
-                    #[cfg(all(test, not(miri)))]
-                    cov_mark::hit!(merge_next);
+            (_, Some(next)) if Size::try_from(size.wrapping_add(next.total_size())).is_ok() => {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(merge_next);
 
-                    return (None, Some(next));
-                }
+                return (None, Some(next));
             }
 
             _ => {}
@@ -76,28 +80,93 @@ impl<const FLL: usize> Header<FLL> {
 
         (None, None)
     }
+
+    /// Merges `block` with the next physical block, if that one is free and the merged block
+    /// does not exceed `MAX_USABLE_SIZE`
+    ///
+    /// The next block of a free block is normally never free but splitting a free block that was
+    /// too large to merge with its next block can produce a block that is small enough to merge
+    pub(super) unsafe fn coalesce_next<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: FreeBlock<'a>,
+    ) -> FreeBlock<'a> {
+        let size = block.usable_size() as usize;
+        let next = anchor
+            .next_phys_block(&block)
+            .and_then(|block| block.try_into_free(anchor))
+            .filter(|next| Size::try_from(size.wrapping_add(next.total_size())).is_ok());
+
+        if let Some(next) = next {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(coalesce_next_after_split);
+
+            self.unlink(anchor, &next);
+            block.merge(anchor, &next);
+            // the header may lie in memory that `Tlsf::malloc_zeroed` knows to be zeroed
+            next.clear();
+        }
+
+        block
+    }
+
+    /// Merges `block` with the previous physical block, if that one is free and the merged block
+    /// does not exceed `MAX_USABLE_SIZE`
+    ///
+    /// Splitting the start off a free block that was too large to merge with its previous block
+    /// can produce a block that is small enough to merge
+    pub(super) unsafe fn coalesce_prev<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: FreeBlock<'a>,
+    ) -> FreeBlock<'a> {
+        let size = block.usable_size() as usize;
+        let prev = anchor
+            .prev_phys_block(&block)
+            .and_then(|block| block.try_into_free(anchor))
+            .filter(|prev| Size::try_from(size.wrapping_add(prev.total_size())).is_ok());
+
+        match prev {
+            Some(prev) => {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(coalesce_prev_after_split);
+
+                self.unlink(anchor, &prev);
+                prev.merge(anchor, &block);
+                // the header may lie in memory that `Tlsf::malloc_zeroed` knows to be zeroed
+                block.clear();
+                prev
+            }
+            None => block,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::mem::MaybeUninit;
+    use core::alloc::Layout;
 
     use super::*;
-    use crate::helpers::FreeBlocks;
+    use crate::block::UsedBlock;
+    use crate::consts::{self, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::{pool_len, Aligned, FreeBlocks, Memory};
+    use crate::ops::util;
+
+    const TOTAL: Size = UsedBlock::HEADER_SIZE as Size + MIN;
 
     #[test]
     fn next() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
 
-            let second = free_blocks.next(4, true);
+            let second = free_blocks.next(MIN, true);
             header.push(anchor, second);
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             {
                 #[cfg(not(miri))]
@@ -105,11 +174,11 @@ mod tests {
 
                 header.coalesce(anchor, first.clone());
             }
-            assert!(header.is_sl_bit_set(0, 3));
+            assert!(header.is_list_set(MIN + TOTAL));
             assert!(header.is_fl_bit_set(0));
 
             let [block] = header.free_blocks(anchor).try_into().unwrap();
-            assert_eq!(12, block.usable_size());
+            assert_eq!(MIN + TOTAL, block.usable_size());
             assert_eq!(anchor.offset_of(&block), anchor.offset_of(&first));
         }
     }
@@ -117,16 +186,16 @@ mod tests {
     #[test]
     fn prev() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first.clone());
 
-            let second = free_blocks.next(4, true);
+            let second = free_blocks.next(MIN, true);
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             {
                 #[cfg(not(miri))]
@@ -134,11 +203,11 @@ mod tests {
 
                 header.coalesce(anchor, second);
             }
-            assert!(header.is_sl_bit_set(0, 3));
+            assert!(header.is_list_set(MIN + TOTAL));
             assert!(header.is_fl_bit_set(0));
 
             let [block] = header.free_blocks(anchor).try_into().unwrap();
-            assert_eq!(12, block.usable_size());
+            assert_eq!(MIN + TOTAL, block.usable_size());
             assert_eq!(anchor.offset_of(&block), anchor.offset_of(&first));
         }
     }
@@ -146,19 +215,19 @@ mod tests {
     #[test]
     fn both_sides() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 7];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first.clone());
 
-            let second = free_blocks.next(4, false);
+            let second = free_blocks.next(MIN, false);
 
-            let third = free_blocks.next(4, true);
+            let third = free_blocks.next(MIN, true);
             header.push(anchor, third);
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             {
                 #[cfg(not(miri))]
@@ -166,11 +235,11 @@ mod tests {
 
                 header.coalesce(anchor, second);
             }
-            assert!(header.is_sl_bit_set(0, 5));
+            assert!(header.is_list_set(MIN + 2 * TOTAL));
             assert!(header.is_fl_bit_set(0));
 
             let [block] = header.free_blocks(anchor).try_into().unwrap();
-            assert_eq!(20, block.usable_size());
+            assert_eq!(MIN + 2 * TOTAL, block.usable_size());
             assert_eq!(anchor.offset_of(&block), anchor.offset_of(&first));
         }
     }
@@ -178,26 +247,86 @@ mod tests {
     #[test]
     fn no_merge() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 7];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             first.into_used(anchor);
 
-            let second = free_blocks.next(4, false);
+            let second = free_blocks.next(MIN, false);
 
-            let third = free_blocks.next(4, true);
+            let third = free_blocks.next(MIN, true);
             third.into_used(anchor);
 
             assert!(!header.is_fl_bit_set(0));
             header.coalesce(anchor, second.clone());
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
 
             let [block] = header.free_blocks(anchor).try_into().unwrap();
-            assert_eq!(4, block.usable_size());
+            assert_eq!(MIN, block.usable_size());
             assert_eq!(anchor.offset_of(&block), anchor.offset_of(&second));
         }
     }
+
+    #[test]
+    fn next_after_split() {
+        let mut header = Header::<{ consts::MAX_FLL }>::new();
+        let mut memory = Memory::new();
+        let mut free_blocks = FreeBlocks::new(memory.bytes());
+        let anchor = free_blocks.anchor;
+        unsafe {
+            // too large to merge, unless `Size` is 32-bit
+            let first = free_blocks.next(30_000, false);
+            header.push(anchor, first);
+            let second = free_blocks.next(40_000, true);
+            header.push(anchor, second);
+
+            // the remainder of `first` is small enough to merge with `second`
+            {
+                #[cfg(not(miri))]
+                cov_mark::check!(coalesce_next_after_split);
+
+                header.malloc(anchor, 20_000.try_into().unwrap()).unwrap();
+            }
+
+            let [block] = header.free_blocks(anchor).try_into().unwrap();
+            let used = util::round_up_block_size(20_000).unwrap();
+            assert_eq!(30_000 - used + 40_000, block.usable_size());
+            assert!(block.is_last_phys_block());
+        }
+    }
+
+    #[test]
+    fn prev_after_split() {
+        let mut header = Header::<{ consts::MAX_FLL }>::new();
+        let mut memory = Memory::new();
+        let mut free_blocks = FreeBlocks::new(memory.bytes());
+        let anchor = free_blocks.anchor;
+        unsafe {
+            // too large to merge, unless `Size` is 32-bit
+            let first = free_blocks.next(30_000, false);
+            let offset = anchor.offset_of(&first);
+            header.push(anchor, first);
+            let second = free_blocks.next(40_000, true);
+            header.push(anchor, second);
+
+            // only `second` is large enough; the padding split off its start to align the
+            // allocation is small enough to merge with `first`
+            let layout = Layout::from_size_align(32_000, 4096).unwrap();
+            let alloc = {
+                #[cfg(not(miri))]
+                cov_mark::check!(coalesce_prev_after_split);
+
+                header.memalign(anchor, layout).unwrap()
+            };
+
+            let [block, _] = header.free_blocks(anchor).try_into().unwrap();
+            assert_eq!(offset, anchor.offset_of(&block));
+            let end = block.body_ptr().as_ptr() as usize + block.usable_size() as usize;
+            let alloc_header = alloc.as_ptr() as usize - usize::from(UsedBlock::HEADER_SIZE);
+            assert_eq!(alloc_header, end);
+        }
+    }
 }
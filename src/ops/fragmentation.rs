@@ -0,0 +1,120 @@
+use crate::header::Header;
+use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
+use crate::{Fragmentation, FreeSpan, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Computes fragmentation metrics and collects the `N` largest free blocks
+    ///
+    /// This walks all the memory blocks and executes in linear time (`O(n * N)`). It doesn't
+    /// allocate memory
+    pub fn fragmentation<const N: usize>(&self) -> Fragmentation<FLL, SL, N> {
+        let mut report = Fragmentation {
+            free_bytes: 0,
+            free_blocks: 0,
+            largest_free_block: 0,
+            histogram: [[0; SL]; FLL],
+            largest_spans: [None; N],
+        };
+
+        for block in self.blocks().filter(|block| block.is_free()) {
+            let size = block.usable_size();
+            report.free_bytes += size as usize;
+            report.free_blocks += 1;
+            report.largest_free_block = report.largest_free_block.max(size);
+
+            let Indices { fl, sl } = Header::<FLL, SL>::mapping_insert(size);
+            report.histogram[usize::from(fl)][usize::from(sl)] += 1;
+
+            // insertion sort; ties keep the lowest offset first
+            let mut span = Some(FreeSpan {
+                offset: block.offset(),
+                size,
+            });
+            for slot in &mut report.largest_spans {
+                // `Option::is_none_or` needs a newer toolchain
+                #[allow(clippy::unnecessary_map_or)]
+                let is_smaller = slot.map_or(true, |other| other.size < size);
+                if is_smaller {
+                    span = core::mem::replace(slot, span);
+                    if span.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::consts::{self, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::Aligned;
+    use crate::Size;
+
+    const GRANULARITY: Size = consts::BLOCK_ALIGN as Size;
+
+    #[test]
+    fn empty() {
+        let tlsf = Tlsf::<2>::empty();
+        let report = tlsf.fragmentation::<2>();
+        assert_eq!(0, report.free_blocks);
+        assert_eq!(0., report.external_ratio());
+        assert_eq!([None, None], report.largest_spans);
+    }
+
+    #[test]
+    fn it_works() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 16 * GRANULARITY as usize }>::new();
+        tlsf.initialize(&mut memory);
+
+        let report = tlsf.fragmentation::<2>();
+        assert_eq!(1, report.free_blocks);
+        assert_eq!(report.free_bytes, report.largest_free_block as usize);
+        assert_eq!(0., report.external_ratio());
+
+        // leave behind free blocks of three different usable sizes, and the tail
+        let mut allocs = vec![];
+        for size in [MIN, MIN, MIN + 2 * GRANULARITY, MIN, MIN + GRANULARITY, MIN] {
+            allocs.push(tlsf.malloc(size.try_into().unwrap()).unwrap());
+        }
+        let [a, _, b, _, c, _] = allocs.try_into().unwrap();
+        for alloc in [a, b, c] {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
+
+        let free = tlsf
+            .blocks()
+            .filter(|block| block.is_free())
+            .map(|block| (block.offset(), block.usable_size()))
+            .collect::<Vec<_>>();
+        assert_eq!(4, free.len());
+        let tail = free[3];
+
+        let report = tlsf.fragmentation::<3>();
+        assert_eq!(tlsf.stats().free_bytes, report.free_bytes);
+        assert_eq!(4, report.free_blocks);
+        assert_eq!(tail.1, report.largest_free_block);
+        let expected = 1. - tail.1 as f32 / report.free_bytes as f32;
+        assert_eq!(expected, report.external_ratio());
+
+        let spans = report
+            .largest_spans
+            .map(|span| span.map(|span| (span.offset, span.size)));
+        assert_eq!([Some(tail), Some(free[1]), Some(free[2])], spans);
+
+        for bin in tlsf.bins() {
+            let count = report.histogram[usize::from(bin.fl())][usize::from(bin.sl())];
+            assert_eq!(bin.blocks().count(), count);
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use core::ptr::NonNull;
 
-use crate::Tlsf;
+use crate::block::{Anchor, UsedBlock};
+use crate::sl::{SecondLevel, Sl};
+use crate::{FreeError, Tlsf};
 
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Returns the block of memory behind `ptr` to the allocator
     ///
     /// # Safety
@@ -11,8 +16,64 @@ impl<'a, const FLL: usize> Tlsf<'a, FLL> {
     /// - `ptr` MUST no be freed more than once
     /// - `ptr` MUST not be used after it has been freed
     pub unsafe fn free(&mut self, ptr: NonNull<u32>) {
-        let Some(anchor) = self.anchor else { return };
-        let used = anchor.get_used_block(ptr);
+        let Some(anchor) = self.anchor() else { return };
+        let Some(used) = anchor.get_used_block(ptr) else {
+            return;
+        };
+        self.free_block(anchor, ptr, used);
+    }
+
+    /// Returns the block of memory behind `ptr` to the allocator after checking that `ptr` denotes
+    /// an allocated block
+    ///
+    /// This behaves like [`Tlsf::free`] but rejects, leaving the heap untouched, pointers outside
+    /// the pools of the allocator, pointers that are misaligned, that don't point at the start of a
+    /// block or whose block is already free. The checks are best effort: they only read the headers
+    /// of the block and of its physical neighbors, so this operation executes in bounded constant
+    /// time (`O(1)`) but doesn't catch an interior pointer whose preceding words look like a valid
+    /// block header.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` MUST not be used after it has been freed
+    /// - the contents of an allocation MUST not mimic a block header; an interior pointer into such
+    ///   an allocation can pass the checks
+    pub unsafe fn try_free(&mut self, ptr: NonNull<u32>) -> Result<(), FreeError> {
+        let anchor = self.anchor().ok_or(FreeError::ForeignPointer)?;
+        let used = anchor.find_used_block(ptr)?;
+        self.free_block(anchor, ptr, used);
+        Ok(())
+    }
+
+    /// # Safety
+    /// - `used` must be the block behind `ptr` and must be covered by `anchor`
+    unsafe fn free_block(&mut self, anchor: Anchor<'a>, ptr: NonNull<u32>, used: UsedBlock<'a>) {
+        // the rest of the checks trust the size in the header
+        #[cfg(feature = "header-checksum")]
+        if !self.header.check_checksum(anchor, &used) {
+            return;
+        }
+
+        #[cfg(feature = "hardening")]
+        if used.is_quarantined(anchor) {
+            self.header.report(crate::Corruption::DoubleFree {
+                block: anchor.offset_of(&used).get(),
+            });
+            return;
+        }
+
+        #[cfg(feature = "hardening")]
+        self.header.check_canary(anchor, &used);
+
+        self.header.count_free();
+        self.mark_freed_dirty(ptr, used.usable_size());
+
+        #[cfg(feature = "hardening")]
+        if self.header.quarantine_block(anchor, &used) {
+            return;
+        }
+
+        self.header.untrack_used(used.usable_size());
         let free = used.into_free(anchor);
         self.header.coalesce(anchor, free);
     }
@@ -21,16 +82,23 @@ impl<'a, const FLL: usize> Tlsf<'a, FLL> {
 #[cfg(test)]
 mod tests {
     use core::alloc::Layout;
-    use core::mem::MaybeUninit;
 
     use super::*;
-    use crate::block::{Anchor, Offset};
+    use crate::block::UsedBlock;
+    #[cfg(not(feature = "wide-offsets"))]
+    use crate::block::{Offset, Pool};
+    use crate::consts::{self, MIN_USABLE_SIZE as MIN};
+    #[cfg(not(feature = "wide-offsets"))]
     use crate::helpers::Memory;
+    use crate::helpers::{pool_len, Aligned};
+    use crate::Size;
+
+    const GRANULARITY: usize = consts::BLOCK_ALIGN as usize;
 
     #[test]
     fn it_works() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 4];
+        let mut memory = Aligned::<{ pool_len(&[2 * MIN]) }>::new();
         tlsf.initialize(&mut memory);
 
         let layout = Layout::new::<u32>();
@@ -43,17 +111,79 @@ mod tests {
         unsafe { tlsf.free(NonNull::from(alloc).cast()) }
     }
 
+    // with wide offsets, the offset space is too large to allocate
+    #[cfg(not(feature = "wide-offsets"))]
     #[test]
     fn furthest_block() {
         let mut tlsf = Tlsf::<1>::empty();
         let mut memory = Memory::new();
-        let anchor = Anchor::new(memory.bytes());
-        tlsf.anchor = Some(anchor);
+        let memory = memory.bytes();
+        let len = memory.len() * 4;
+        tlsf.pools[0] = unsafe { Pool::new(memory, 0, len) };
+        tlsf.pool_count = 1;
+        let anchor = tlsf.anchor().unwrap();
 
         unsafe {
-            let block = anchor.create_free_block(Offset::max(), 4, true, None);
+            let block = anchor.create_free_block(Offset::max(), MIN, true, None);
+            tlsf.header.track_used(block.usable_size());
             let alloc = block.into_used(anchor);
             tlsf.free(NonNull::from(alloc).cast());
         }
     }
+
+    #[test]
+    fn try_free() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 8 * GRANULARITY }>::new();
+        let mut foreign = [0_u32; 4];
+        assert_eq!(Err(FreeError::ForeignPointer), unsafe {
+            tlsf.try_free(NonNull::from(&mut foreign).cast())
+        });
+        let pool_start = NonNull::from(&mut memory).cast();
+        tlsf.initialize(&mut memory);
+
+        let size = Size::from(2 * consts::BLOCK_ALIGN).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        let second = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _third = tlsf.malloc(size).unwrap();
+        first.iter_mut().for_each(|mu| {
+            mu.write(0);
+        });
+        let first = NonNull::from(first).cast::<u32>();
+
+        unsafe {
+            assert_eq!(
+                Err(FreeError::ForeignPointer),
+                tlsf.try_free(NonNull::from(&mut foreign).cast())
+            );
+            assert_eq!(
+                Err(FreeError::Misaligned),
+                tlsf.try_free(first.cast::<u8>().add(1).cast())
+            );
+            // the body of `first` is zeroed so, past a header's worth of bytes, it looks like the body
+            // of a used block
+            let header = usize::from(UsedBlock::HEADER_SIZE);
+            assert_eq!(
+                Err(FreeError::NotABlock),
+                tlsf.try_free(first.byte_add(header))
+            );
+            // points at the header of the first block
+            assert_eq!(
+                Err(FreeError::NotABlock),
+                tlsf.try_free(first.byte_sub(header))
+            );
+            // points at the start of the pool, before the first block
+            assert_eq!(Err(FreeError::NotABlock), tlsf.try_free(pool_start));
+
+            assert_eq!(Ok(()), tlsf.try_free(second));
+            assert_eq!(Err(FreeError::DoubleFree), tlsf.try_free(second));
+
+            // `first` merges with the free block that follows it
+            assert_eq!(Ok(()), tlsf.try_free(first));
+            assert_eq!(Err(FreeError::DoubleFree), tlsf.try_free(second));
+        }
+
+        assert_eq!(Ok(()), tlsf.check());
+        assert_eq!(2, tlsf.stats().frees);
+    }
 }
@@ -0,0 +1,186 @@
+use crate::block::{Anchor, UsedBlock};
+use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::{Corruption, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Sets the function the allocator calls when it detects heap damage
+    ///
+    /// The allocator checks the canary kept in the last word of each allocated block when the
+    /// allocation is freed or resized, and checks that a free block still holds the poison pattern
+    /// when the block leaves its free list or the quarantine (see [`Tlsf::set_quarantine`]). On a
+    /// mismatch, it calls `hook` with [`Corruption::Overrun`] or [`Corruption::UseAfterFree`] and
+    /// then carries on with the operation. Freeing a quarantined block again is reported as
+    /// [`Corruption::DoubleFree`] and ignored. The `safe-linking` and `header-checksum` features
+    /// report more damage, see `Tlsf::set_secret`.
+    ///
+    /// The canary sits at the end of the block, not right after the requested size: requests are
+    /// rounded up to the block granularity so an overrun that stays within that slack goes
+    /// undetected.
+    ///
+    /// `hook` runs in the middle of an allocator operation so it must not use the allocator again,
+    /// e.g. through a `TlsfCell` or a global allocator backed by it. The default hook does nothing
+    pub fn set_corruption_hook(&mut self, hook: fn(Corruption)) {
+        self.header.set_hook(hook);
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Reports an overrun if the canary of `block` was overwritten
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    pub(super) unsafe fn check_canary<'a>(&self, anchor: Anchor<'a>, block: &UsedBlock<'a>) {
+        if !block.has_canary(anchor) {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(hardening_overrun);
+
+            self.report(Corruption::Overrun {
+                block: anchor.offset_of(block).get(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use core::alloc::Layout;
+    use core::cell::RefCell;
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::block::FreeBlock;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{pool_len, Aligned};
+    use crate::ops::util;
+    use crate::Size;
+
+    const GRANULARITY: Size = consts::BLOCK_ALIGN as Size;
+
+    std::thread_local! {
+        static REPORTS: RefCell<Vec<Corruption>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(crate) fn record(corruption: Corruption) {
+        REPORTS.with(|reports| reports.borrow_mut().push(corruption));
+    }
+
+    pub(crate) fn reports() -> Vec<Corruption> {
+        REPORTS.with(|reports| reports.take())
+    }
+
+    #[test]
+    fn intact() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        // one block per allocation below, with any block granularity
+        let mut memory = Aligned::<{ pool_len(&[4 * GRANULARITY; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let mut allocs = vec![];
+        for size in [1, 4, 8, 12] {
+            let alloc = tlsf.malloc(size.try_into().unwrap()).unwrap();
+            // the canary word is not part of the allocation
+            assert!(alloc.len() * 4 >= size as usize);
+            alloc.iter_mut().for_each(|mu| {
+                mu.write(!0);
+            });
+            allocs.push(alloc);
+        }
+        for alloc in allocs {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
+        assert!(tlsf.malloc(64.try_into().unwrap()).is_some());
+
+        assert!(reports().is_empty());
+        assert_eq!(Ok(()), tlsf.check());
+    }
+
+    #[test]
+    fn overrun() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc(4.try_into().unwrap()).unwrap();
+        let block = tlsf.blocks().next().unwrap().offset();
+
+        // writes one word past the end of the allocation
+        let len = alloc.len();
+        unsafe { alloc.as_mut_ptr().add(len).write(MaybeUninit::new(0)) }
+
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(hardening_overrun);
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
+        assert_eq!([Corruption::Overrun { block }], *reports());
+
+        // the block was freed nonetheless
+        assert_eq!(0, tlsf.stats().used_blocks);
+    }
+
+    #[test]
+    fn use_after_free() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        const SIZE: Size = 4 * GRANULARITY;
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 2]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        let _second = tlsf.malloc(size).unwrap();
+        let block = tlsf.blocks().next().unwrap().offset();
+        let dangling = first.as_mut_ptr();
+        unsafe { tlsf.free(NonNull::from(first).cast()) }
+        assert!(reports().is_empty());
+
+        // writes past the free list links of the freed block
+        let links_len = (FreeBlock::HEADER_SIZE - UsedBlock::HEADER_SIZE) as usize / 4;
+        unsafe { dangling.add(links_len).write(MaybeUninit::new(0)) }
+
+        assert!(tlsf.malloc(size).is_some());
+        assert_eq!([Corruption::UseAfterFree { block }], *reports());
+    }
+
+    #[test]
+    fn realloc_move() {
+        // the allocation below, then room for an 8-byte allocation aligned to 64 bytes
+        const LEN: usize = pool_len(&[4 * GRANULARITY, 128 + FreeBlock::HEADER_SIZE as Size]);
+
+        #[repr(align(64))]
+        struct Aligned64([MaybeUninit<u32>; LEN]);
+
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        let mut memory = Aligned64([MaybeUninit::uninit(); LEN]);
+        tlsf.initialize(&mut memory.0);
+
+        let alloc = tlsf.malloc(12.try_into().unwrap()).unwrap();
+        alloc.iter_mut().for_each(|mu| {
+            mu.write(!0);
+        });
+
+        // the body of the first block is not 64-byte aligned so the block moves; the new block is
+        // smaller than the old one and only the allocation, not the canary, must be copied over
+        let layout = Layout::from_size_align(8, 64).unwrap();
+        let moved =
+            unsafe { tlsf.realloc(NonNull::from(alloc).cast(), layout, usize::MAX) }.unwrap();
+        let expected = util::round_up_block_size(8).unwrap() - CANARY;
+        assert_eq!(expected as usize / 4, moved.len());
+        assert!(moved.iter().all(|mu| unsafe { mu.assume_init() } == !0));
+
+        unsafe { tlsf.free(NonNull::from(moved).cast()) }
+        assert!(reports().is_empty());
+    }
+}
@@ -1,43 +1,132 @@
 use core::mem::{self, MaybeUninit};
 
-use crate::block::{Anchor, FreeBlock, Offset, UsedBlock};
-use crate::{consts, Tlsf};
+use crate::block::{FreeBlock, Offset, Pool, UsedBlock};
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, PoolError, PoolReport, Tlsf};
 
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Gives the allocator a chunk of memory to manage
     ///
     /// The allocator MAY only be initialized once. Subsequent invocations of this method will be
-    /// ignored.
+    /// ignored. Use [`Tlsf::add_pool`] to give the allocator more memory to manage.
+    ///
+    /// Use [`Tlsf::try_initialize`] to find out what the allocator did with `memory`
     pub fn initialize(&mut self, memory: &'a mut [MaybeUninit<u32>]) {
-        if self.anchor.is_some() {
+        let _ = self.try_initialize(memory);
+    }
+
+    /// Gives the allocator a chunk of memory to manage
+    ///
+    /// This behaves like [`Tlsf::initialize`] but reports how much of `memory` the allocator will
+    /// use, or why it ignored `memory`
+    pub fn try_initialize(
+        &mut self,
+        memory: &'a mut [MaybeUninit<u32>],
+    ) -> Result<PoolReport, PoolError> {
+        if self.pool_count != 0 {
             #[cfg(all(test, not(miri)))]
             cov_mark::hit!(initialized_twice);
 
-            return;
+            return Err(PoolError::AlreadyInitialized);
         }
 
-        let mut total_size = memory
-            .len()
-            .saturating_mul(mem::size_of::<u32>())
-            .min(consts::MAX_POOL_SIZE);
+        self.try_add_pool(memory)
+    }
+
+    /// Gives the allocator an additional chunk of memory to manage
+    ///
+    /// The memory does not need to be contiguous with the memory of the previously added pools.
+    /// All the pools share the same free lists but an allocation never spans more than one pool.
+    ///
+    /// The combined size of all the pools can't exceed 256 KiB, unless the `wide-offsets` feature
+    /// is enabled; memory beyond that limit is not used.
+    ///
+    /// This function returns `false`, and ignores `memory`, when `POOLS` pools have already been
+    /// added and when `memory` is too small to hold a single memory block. Use
+    /// [`Tlsf::try_add_pool`] to find out what the allocator did with `memory`
+    pub fn add_pool(&mut self, memory: &'a mut [MaybeUninit<u32>]) -> bool {
+        self.try_add_pool(memory).is_ok()
+    }
 
-        // skip the first `BLOCK_ALIGN` bytes to ensure `Offset` is a non-zero value
-        let mut uncompressed_offset = usize::from(consts::BLOCK_ALIGN);
+    /// Gives the allocator an additional chunk of memory to manage
+    ///
+    /// This behaves like [`Tlsf::add_pool`] but reports how much of `memory` the allocator will
+    /// use, or why it ignored `memory`
+    pub fn try_add_pool(
+        &mut self,
+        memory: &'a mut [MaybeUninit<u32>],
+    ) -> Result<PoolReport, PoolError> {
+        let base = self
+            .pools
+            .get(..self.pool_count)
+            .and_then(|pools| pools.last())
+            .map(Pool::end)
+            .unwrap_or(0);
+
+        let Some(slot) = self.pools.get_mut(self.pool_count) else {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(add_pool_no_room);
+
+            return Err(PoolError::NoRoom);
+        };
+
+        let given_size = memory.len().saturating_mul(mem::size_of::<u32>());
+
+        // block headers must be `BLOCK_ALIGN`-byte aligned
+        let block_align = usize::from(consts::BLOCK_ALIGN);
+        let misalignment = memory.as_ptr().align_offset(block_align);
+        let Some(memory) = memory.get_mut(misalignment..) else {
+            return Err(PoolError::TooSmall);
+        };
+
+        // pools end on a `BLOCK_ALIGN` boundary so that the next pool starts on one
+        let aligned_size = memory.len().saturating_mul(mem::size_of::<u32>()) & !(block_align - 1);
+        let offset_space = consts::MAX_OFFSET_SPACE.saturating_sub(base) & !(block_align - 1);
+        let mut total_size = aligned_size.min(offset_space);
+        let truncated_bytes = aligned_size - total_size;
+
+        let skipped_size = Pool::skipped_size(base);
 
         let free_header_size = usize::from(FreeBlock::HEADER_SIZE);
-        if total_size < uncompressed_offset + free_header_size {
-            return;
+        if total_size < skipped_size + free_header_size {
+            return Err(if truncated_bytes != 0 {
+                // the offset space is too large to exhaust in the tests with `wide-offsets`
+                #[cfg(all(test, not(miri), not(feature = "wide-offsets")))]
+                cov_mark::hit!(add_pool_offset_space_exhausted);
+
+                PoolError::OffsetSpaceExhausted
+            } else {
+                PoolError::TooSmall
+            });
         }
 
-        total_size -= uncompressed_offset;
+        let pool = unsafe { Pool::new(memory, base, total_size) };
+        *slot = pool;
+        self.pool_count += 1;
+
+        let Some(anchor) = self.anchor() else {
+            return Err(PoolError::NoRoom);
+        };
 
-        let anchor = Anchor::new(memory);
         let used_header_size = usize::from(UsedBlock::HEADER_SIZE);
 
+        let mut uncompressed_offset = base + skipped_size;
+        total_size -= skipped_size;
+
+        let mut report = PoolReport {
+            managed_bytes: 0,
+            discarded_bytes: 0,
+            truncated_bytes,
+            blocks: 0,
+        };
         let mut prev_phys_block = None;
         while total_size >= free_header_size {
             let usable_size = (total_size - used_header_size).try_into().unwrap_or({
-                #[cfg(all(test, not(miri)))]
+                // with wide sizes, the tests can't allocate a pool that needs a block this large
+                #[cfg(all(test, not(miri), not(feature = "wide-sizes")))]
                 cov_mark::hit!(initialize_max_usable_size);
 
                 consts::MAX_USABLE_SIZE
@@ -45,14 +134,14 @@ impl<'a, const FLL: usize> Tlsf<'a, FLL> {
 
             let offset = unsafe { Offset::compress(uncompressed_offset) };
 
-            let step = usize::from(usable_size) + used_header_size;
-            // due to the `MAX_POOL_SIZE` cap we know this won't cause an overflow (see tests)
+            let step = usable_size as usize + used_header_size;
+            // due to the `MAX_OFFSET_SPACE` cap we know this won't cause an overflow (see tests)
             // LLVM can't figure that out and keeps the overflow checks so we help it here with
             // the `wrapping_` operations
             #[cfg(not(any(fuzzing, test)))]
             {
                 total_size = total_size.wrapping_sub(step);
-                uncompressed_offset = uncompressed_offset.wrapping_sub(step);
+                uncompressed_offset = uncompressed_offset.wrapping_add(step);
             }
 
             // the version with overflow checks is used to detect bugs in the `stress` test
@@ -69,56 +158,98 @@ impl<'a, const FLL: usize> Tlsf<'a, FLL> {
             prev_phys_block = Some(offset);
 
             unsafe { self.header.push(anchor, block) }
+
+            report.managed_bytes += step;
+            report.blocks += 1;
+        }
+
+        report.discarded_bytes = given_size - report.managed_bytes;
+
+        if self.pool_count == 1 {
+            self.reset_watermarks();
         }
 
-        self.anchor = Some(anchor);
+        Ok(report)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use core::ptr::NonNull;
+
     use super::*;
+    use crate::consts::{CANARY_SIZE, MIN_USABLE_SIZE as MIN};
+    #[cfg(not(feature = "wide-offsets"))]
+    use crate::helpers::Memory;
+    use crate::helpers::{added_pool_len, pool_len, Aligned, MIN_ALLOC};
+    use crate::Size;
+
+    const TOTAL: Size = UsedBlock::HEADER_SIZE as Size + MIN;
 
     #[test]
     fn no_anchor() {
         let mut tlsf = Tlsf::<1>::empty();
         let mut memory = [MaybeUninit::uninit(); 2];
         tlsf.initialize(&mut memory);
-        assert!(tlsf.anchor.is_none());
+        assert!(tlsf.anchor().is_none());
         assert!(tlsf.free_blocks().is_empty());
     }
 
     #[test]
     fn one() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 3];
-        tlsf.initialize(&mut memory);
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
+        let report = tlsf.try_initialize(&mut memory).unwrap();
 
         let blocks = tlsf.free_blocks();
         assert_eq!(1, blocks.len());
-        assert_eq!(4, blocks[0].usable_size());
+        assert_eq!(MIN, blocks[0].usable_size());
+
+        assert_eq!(
+            PoolReport {
+                managed_bytes: TOTAL as usize,
+                discarded_bytes: consts::BLOCK_ALIGN.into(),
+                truncated_bytes: 0,
+                blocks: 1,
+            },
+            report
+        );
+    }
+
+    #[test]
+    fn too_small() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN]) - 1 }>::new();
+        assert_eq!(Err(PoolError::TooSmall), tlsf.try_initialize(&mut memory));
     }
 
     #[test]
     fn initialized_twice() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         tlsf.initialize(&mut memory);
 
         {
             #[cfg(not(miri))]
             cov_mark::check!(initialized_twice);
-            tlsf.initialize(&mut []);
+            assert_eq!(
+                Err(PoolError::AlreadyInitialized),
+                tlsf.try_initialize(&mut [])
+            );
         }
     }
 
+    // with wide sizes, a block can span the whole offset space
+    #[cfg(not(feature = "wide-sizes"))]
     #[test]
     fn max_usable_size() {
         #[cfg(not(miri))]
         cov_mark::check!(initialize_max_usable_size);
 
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 16 * 1024 + 2];
+        // one granule more than the largest block needs
+        const LEN: usize = pool_len(&[consts::MAX_USABLE_SIZE]) + consts::BLOCK_ALIGN as usize / 4;
+        let mut memory = Aligned::<LEN>::new();
         tlsf.initialize(&mut memory);
 
         let blocks = tlsf.free_blocks();
@@ -127,11 +258,14 @@ mod tests {
         assert_eq!(consts::MAX_USABLE_SIZE, blocks[0].usable_size());
     }
 
+    #[cfg(not(feature = "wide-sizes"))]
     #[test]
     fn two() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 16 * 1024 + 3];
-        tlsf.initialize(&mut memory);
+        let mut memory = Aligned::<{ pool_len(&[consts::MAX_USABLE_SIZE, MIN]) }>::new();
+        let report = tlsf.try_initialize(&mut memory).unwrap();
+        assert_eq!(2, report.blocks);
+        assert_eq!(usize::from(consts::BLOCK_ALIGN), report.discarded_bytes);
         let blocks = tlsf.free_blocks();
         assert_eq!(2, blocks.len());
         let [a, b] = blocks.try_into().unwrap();
@@ -139,25 +273,185 @@ mod tests {
         assert_eq!(consts::MAX_USABLE_SIZE, a.usable_size());
         assert!(!a.is_last_phys_block());
 
-        assert_eq!(4, b.usable_size());
-        assert!(b.is_last_phys_block());
+        assert_eq!(MIN, b.usable_size());
     }
 
+    // the offset space is too large to fill with `wide-offsets`
+    #[cfg(not(feature = "wide-offsets"))]
     #[cfg(not(miri))] // slow
     #[test]
     fn max_pool_size() {
         let mut tlsf = Tlsf::<1>::empty();
         let mut memory =
             vec![MaybeUninit::<u32>::uninit(); consts::MAX_POOL_SIZE].into_boxed_slice();
+        let report = tlsf.try_initialize(&mut memory[..]).unwrap();
+        // with wide sizes, a single block spans the whole pool; otherwise the offset space is
+        // `BLOCK_ALIGN` times larger than the largest block
+        let align = usize::from(consts::BLOCK_ALIGN);
+        let blocks = if cfg!(feature = "wide-sizes") {
+            1
+        } else {
+            align
+        };
+        assert_eq!(blocks, report.blocks);
+        assert_eq!(consts::MAX_OFFSET_SPACE - align, report.managed_bytes);
+        assert_eq!(
+            consts::MAX_POOL_SIZE * 4 - consts::MAX_OFFSET_SPACE,
+            report.truncated_bytes
+        );
+        assert_eq!(report.truncated_bytes + align, report.discarded_bytes,);
+
+        let free_blocks = tlsf.free_blocks();
+        assert_eq!(blocks, free_blocks.len());
+        let total_usable_size = free_blocks
+            .iter()
+            .map(|block| block.usable_size() as usize)
+            .sum::<usize>();
+        let headers_size = blocks * usize::from(UsedBlock::HEADER_SIZE);
+        assert_eq!(report.managed_bytes - headers_size, total_usable_size);
+    }
+
+    #[cfg(feature = "wide-offsets")]
+    #[cfg(not(miri))] // slow
+    #[test]
+    fn beyond_256_kib() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = vec![MaybeUninit::<u32>::uninit(); 256 * 1024].into_boxed_slice();
+        let start = memory.as_ptr() as usize;
         tlsf.initialize(&mut memory[..]);
 
-        let blocks = tlsf.free_blocks();
-        assert_eq!(4, blocks.len());
-        let total_usable_size = blocks
+        let total_usable_size = tlsf
+            .free_blocks()
             .iter()
             .map(|block| block.usable_size() as usize)
             .sum::<usize>();
-        assert_eq!(262_128, total_usable_size);
+        assert!(total_usable_size > 1_000_000);
+
+        // memory beyond the first 256 KiB is handed out
+        let mut furthest = 0;
+        while let Some(alloc) = tlsf.malloc(60.try_into().unwrap()) {
+            furthest = furthest.max(alloc.as_ptr() as usize - start);
+        }
+        assert!(furthest > 1_000_000);
+    }
+
+    #[test]
+    fn two_pools() {
+        const SMALL: Size = MIN_ALLOC;
+        const LARGE: Size = MIN_ALLOC + TOTAL;
+        let mut first = Aligned::<{ pool_len(&[SMALL]) }>::new();
+        let mut second = Aligned::<{ added_pool_len(&[LARGE]) }>::new();
+        let mut third = Aligned::<{ added_pool_len(&[MIN]) }>::new();
+
+        let mut tlsf = Tlsf::<1, 2>::empty();
+        assert!(tlsf.add_pool(&mut first));
+        assert!(tlsf.add_pool(&mut second));
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(add_pool_no_room);
+
+            assert_eq!(Err(PoolError::NoRoom), tlsf.try_add_pool(&mut third));
+        }
+
+        let blocks = tlsf.blocks().collect::<Vec<_>>();
+        let [a, b] = blocks.try_into().unwrap();
+        assert!(a.is_free());
+        assert_eq!(SMALL, a.usable_size());
+        assert!(b.is_free());
+        assert_eq!(LARGE, b.usable_size());
+
+        let large = tlsf
+            .malloc((LARGE - CANARY_SIZE).try_into().unwrap())
+            .unwrap();
+        let small = tlsf
+            .malloc((SMALL - CANARY_SIZE).try_into().unwrap())
+            .unwrap();
+        assert!(tlsf.free_blocks().is_empty());
+        assert!(tlsf.malloc(MIN.try_into().unwrap()).is_none());
+
+        unsafe {
+            tlsf.free(NonNull::from(small).cast());
+            tlsf.free(NonNull::from(large).cast());
+        }
+
+        // blocks from different pools are never merged
+        let blocks = tlsf.free_blocks();
+        assert_eq!(2, blocks.len());
+        assert_eq!(
+            [SMALL, LARGE],
+            [blocks[0].usable_size(), blocks[1].usable_size()]
+        );
+    }
+
+    #[test]
+    fn pool_too_small() {
+        let mut first = Aligned::<{ pool_len(&[MIN]) - 1 }>::new();
+        let mut second = Aligned::<{ pool_len(&[MIN]) }>::new();
+
+        let mut tlsf = Tlsf::<1, 2>::empty();
+        assert!(!tlsf.add_pool(&mut first));
+        assert!(tlsf.add_pool(&mut second));
+        assert_eq!(1, tlsf.free_blocks().len());
+    }
+
+    // the offset space is too large to fill with `wide-offsets`
+    #[cfg(not(feature = "wide-offsets"))]
+    #[cfg(not(miri))] // slow
+    #[test]
+    fn offset_space_exhausted() {
+        let mut first = Memory::new();
+        let mut second = Aligned::<{ added_pool_len(&[MIN]) }>::new();
+        let mut third = [MaybeUninit::uninit(); 3];
+
+        let mut tlsf = Tlsf::<1, 3>::empty();
+        let len = Offset::MAX_UNCOMPRESSED_VALUE + usize::from(consts::BLOCK_ALIGN);
+        let first = &mut first.bytes()[..len / 4];
+        assert!(tlsf.add_pool(first));
+        // less than `FreeBlock::HEADER_SIZE` bytes left in the offset space
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(add_pool_offset_space_exhausted);
+
+            assert_eq!(
+                Err(PoolError::OffsetSpaceExhausted),
+                tlsf.try_add_pool(&mut second)
+            );
+        }
+        assert!(!tlsf.add_pool(&mut third));
+    }
+
+    // the offset space is too large to fill with `wide-offsets`
+    #[cfg(not(feature = "wide-offsets"))]
+    #[cfg(not(miri))] // slow
+    #[test]
+    fn max_offset_space() {
+        let mut first = Aligned::<{ pool_len(&[MIN]) }>::new();
+        let mut second = Memory::new();
+
+        let mut tlsf = Tlsf::<1, 2>::empty();
+        assert!(tlsf.add_pool(&mut first));
+        assert!(tlsf.add_pool(second.bytes()));
+
+        let total_size = tlsf
+            .free_blocks()
+            .iter()
+            .map(|block| block.total_size())
+            .sum::<usize>();
+        // `BLOCK_ALIGN` bytes are skipped at the start of the first pool only
+        let align = usize::from(consts::BLOCK_ALIGN);
+        assert_eq!(consts::MAX_OFFSET_SPACE - align, total_size);
+
+        // the whole offset space can be used
+        let mut allocs = vec![];
+        while let Some(alloc) = tlsf.malloc(MIN.try_into().unwrap()) {
+            alloc.iter_mut().for_each(|mu| {
+                mu.write(!0);
+            });
+            allocs.push(alloc);
+        }
+        while let Some(alloc) = allocs.pop() {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
     }
 
     // try to trigger any sort of arithmetic overflow
@@ -1,77 +1,181 @@
 use core::mem::MaybeUninit;
-use core::num::NonZeroU16;
 
 use super::util;
 use crate::block::Anchor;
 use crate::header::Header;
-use crate::Tlsf;
+use crate::sl::{SecondLevel, Sl};
+use crate::{AllocError, NonZeroSize, Tlsf};
 
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Allocates a memory block of the requested `size`
     ///
-    /// The returned block is guaranteed to have an alignment of 4 bytes and may exceed the
-    /// requested `size`.
+    /// The returned block is guaranteed to have an alignment of 4 bytes, or 8 / 16 bytes when the
+    /// `align-8` / `align-16` feature is enabled, and may exceed the requested `size`.
     ///
-    /// This function returns `None` when `there's insufficient free memory to satisfy the request
-    pub fn malloc(&mut self, size: NonZeroU16) -> Option<&'a mut [MaybeUninit<u32>]> {
-        let anchor = self.anchor?;
-        unsafe { self.header.malloc(anchor, size) }
+    /// This function returns `None` when there's insufficient free memory to satisfy the request.
+    /// Use [`Tlsf::try_malloc`] to find out why the request failed
+    pub fn malloc(&mut self, size: NonZeroSize) -> Option<&'a mut [MaybeUninit<u32>]> {
+        self.try_malloc(size).ok()
+    }
+
+    /// Allocates a memory block of the requested `size`
+    ///
+    /// This behaves like [`Tlsf::malloc`] but reports the cause of a failure
+    pub fn try_malloc(
+        &mut self,
+        size: NonZeroSize,
+    ) -> Result<&'a mut [MaybeUninit<u32>], AllocError> {
+        let res = self
+            .anchor()
+            .ok_or(AllocError::Uninitialized)
+            .and_then(|anchor| unsafe { self.header.malloc(anchor, size) });
+        let alloc = self.count_alloc(res)?;
+        self.mark_alloc_dirty(alloc);
+        Ok(alloc)
     }
 }
 
-impl<const FLL: usize> Header<FLL> {
-    unsafe fn malloc<'a>(
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    pub(super) unsafe fn malloc<'a>(
         &mut self,
         anchor: Anchor<'a>,
-        size: NonZeroU16,
-    ) -> Option<&'a mut [MaybeUninit<u32>]> {
-        let size = util::round_up_block_size(size.into())?;
+        size: NonZeroSize,
+    ) -> Result<&'a mut [MaybeUninit<u32>], AllocError> {
+        let size = util::round_up_block_size(size.into()).ok_or(AllocError::TooLarge)?;
 
-        let mut block = self.pop(anchor, size)?;
+        let mut block = self.try_pop(anchor, size, size)?;
 
         block = self.adjust_free_block_size(anchor, block, size);
 
         #[cfg(any(fuzzing, test))]
         debug_assert!(block.usable_size() >= size);
 
+        self.track_used(block.usable_size());
         let alloc = block.into_used(anchor);
 
-        Some(alloc)
+        Ok(alloc)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use core::ptr::NonNull;
+
     use super::*;
+    use crate::block::{FreeBlock, UsedBlock};
+    use crate::consts::CANARY_SIZE as CANARY;
+    use crate::helpers::{pool_len, Aligned, MIN_ALLOC as MIN};
+
+    const TOTAL: usize = UsedBlock::HEADER_SIZE as usize + MIN as usize;
 
     #[test]
     fn no_split() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         tlsf.initialize(&mut memory);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(4, free.usable_size());
+        assert_eq!(MIN, free.usable_size());
 
         let alloc = tlsf.malloc(1.try_into().unwrap()).unwrap();
-        assert_eq!(1, alloc.len());
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
         assert!(tlsf.free_blocks().is_empty());
     }
 
     #[test]
     fn split_size() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         tlsf.initialize(&mut memory);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(16, free.total_size());
+        assert_eq!(2 * TOTAL, free.total_size());
 
         let alloc = tlsf.malloc(1.try_into().unwrap()).unwrap();
-        assert_eq!(1, alloc.len());
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
 
         let [remaining] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(8, remaining.total_size());
+        assert_eq!(TOTAL, remaining.total_size());
+    }
+
+    #[test]
+    fn uninitialized() {
+        let mut tlsf = Tlsf::<1>::empty();
+
+        let res = tlsf.try_malloc(1.try_into().unwrap()).err();
+        assert_eq!(Some(AllocError::Uninitialized), res);
+    }
+
+    #[test]
+    fn too_large() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let size = (Header::<1>::MAX_ALLOC_SIZE + 1).try_into().unwrap();
+        let res = {
+            #[cfg(not(miri))]
+            cov_mark::check!(alloc_too_large);
+
+            tlsf.try_malloc(size).err()
+        };
+        assert_eq!(Some(AllocError::TooLarge), res);
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (MIN - CANARY).try_into().unwrap();
+        assert_eq!(
+            Some(AllocError::OutOfMemory {
+                free_bytes: MIN as usize
+            }),
+            tlsf.try_malloc((2 * MIN).try_into().unwrap()).err()
+        );
+
+        tlsf.try_malloc(size).unwrap();
+        assert_eq!(
+            Some(AllocError::OutOfMemory { free_bytes: 0 }),
+            tlsf.try_malloc(size).err()
+        );
+    }
+
+    #[test]
+    fn fragmented() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (MIN - CANARY).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        let _second = tlsf.malloc(size).unwrap();
+        let third = tlsf.malloc(size).unwrap();
+        assert!(tlsf.free_blocks().is_empty());
+
+        unsafe {
+            tlsf.free(NonNull::from(first).cast());
+            tlsf.free(NonNull::from(third).cast());
+        }
+
+        // the used block in between keeps the 2 free blocks from being merged
+        let res = {
+            #[cfg(not(miri))]
+            cov_mark::check!(alloc_fragmented);
+
+            tlsf.try_malloc((2 * MIN - CANARY).try_into().unwrap())
+                .err()
+        };
+        let free_bytes = 2 * MIN as usize;
+        assert_eq!(Some(AllocError::Fragmented { free_bytes }), res);
     }
 
     #[cfg(not(miri))] // slow
@@ -87,7 +191,8 @@ mod tests {
         while let Some(alloc) = tlsf.malloc(size) {
             count += 1;
             assert!(!alloc.is_empty());
-            assert!(alloc.len() <= 2);
+            // the remainder of a block is only split off if it can hold a free block
+            assert!(alloc.len() * 4 < MIN as usize + usize::from(FreeBlock::HEADER_SIZE));
             alloc.iter_mut().for_each(|mu| {
                 mu.write(!0);
             });
@@ -96,4 +201,29 @@ mod tests {
         assert!(tlsf.free_blocks().is_empty());
         assert_eq!(count, tlsf.blocks().count());
     }
+
+    #[cfg(feature = "wide-sizes")]
+    #[test]
+    fn larger_than_62_kib() {
+        let mut tlsf = Tlsf::<13>::empty();
+        let mut memory = vec![MaybeUninit::<u32>::uninit(); 32 * 1024].into_boxed_slice();
+        tlsf.initialize(&mut memory[..]);
+
+        // the whole pool is a single block
+        let [free] = tlsf.free_blocks().try_into().unwrap();
+        let usable_size = free.usable_size();
+        assert!(usable_size > 100 * 1024);
+
+        let size = 100 * 1024;
+        let alloc = tlsf.malloc(size.try_into().unwrap()).unwrap();
+        let expected = util::round_up_block_size(size).unwrap() - CANARY;
+        assert_eq!(expected as usize / 4, alloc.len());
+        alloc.iter_mut().for_each(|mu| {
+            mu.write(!0);
+        });
+
+        unsafe { tlsf.free(core::ptr::NonNull::from(alloc).cast()) }
+        let [merged] = tlsf.free_blocks().try_into().unwrap();
+        assert_eq!(usable_size, merged.usable_size());
+    }
 }
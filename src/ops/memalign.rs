@@ -4,35 +4,64 @@ use core::mem::MaybeUninit;
 use crate::block::{Anchor, FreeBlock};
 use crate::header::Header;
 use crate::ops::util;
-use crate::{consts, Tlsf};
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, AllocError, Size, Tlsf};
 
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// Allocates a memory block compatible with the specified `layout`
     ///
     /// This function returns `None` when `layout` has a `size` equal to zero and when there's
-    /// insufficient free memory to satisfy the request
+    /// insufficient free memory to satisfy the request. Use [`Tlsf::try_memalign`] to find out why
+    /// the request failed
     pub fn memalign(&mut self, layout: Layout) -> Option<&'a mut [MaybeUninit<u32>]> {
-        let anchor = self.anchor?;
-        unsafe { self.header.memalign(anchor, layout) }
+        self.try_memalign(layout).ok()
+    }
+
+    /// Allocates a memory block compatible with the specified `layout`
+    ///
+    /// This behaves like [`Tlsf::memalign`] but reports the cause of a failure
+    pub fn try_memalign(
+        &mut self,
+        layout: Layout,
+    ) -> Result<&'a mut [MaybeUninit<u32>], AllocError> {
+        let res = self
+            .anchor()
+            .ok_or(AllocError::Uninitialized)
+            .and_then(|anchor| unsafe { self.header.memalign(anchor, layout) });
+        let alloc = self.count_alloc(res)?;
+        self.mark_alloc_dirty(alloc);
+        Ok(alloc)
     }
 }
 
-impl<const FLL: usize> Header<FLL> {
-    unsafe fn memalign<'a>(
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    pub(super) unsafe fn memalign<'a>(
         &mut self,
         anchor: Anchor<'a>,
         layout: Layout,
-    ) -> Option<&'a mut [MaybeUninit<u32>]> {
+    ) -> Result<&'a mut [MaybeUninit<u32>], AllocError> {
         if layout.size() == 0 {
-            return None;
+            return Err(AllocError::ZeroSize);
         }
 
-        let size = layout.size().try_into().ok()?;
-        let align = layout.align().try_into().ok()?;
+        let size = layout
+            .size()
+            .try_into()
+            .map_err(|_| AllocError::UnsupportedLayout)?;
+        let align = layout
+            .align()
+            .try_into()
+            .map_err(|_| AllocError::UnsupportedLayout)?;
 
-        let size = util::round_up_block_size(size)?;
-        let worst_case_size = worst_case_size(size, align)?;
-        let mut block = self.pop(anchor, worst_case_size)?;
+        let size = util::round_up_block_size(size).ok_or(AllocError::TooLarge)?;
+        let worst_case_size = worst_case_size(size, align).ok_or(AllocError::TooLarge)?;
+        let mut block = self.try_pop(anchor, worst_case_size, size)?;
 
         block = self.adjust_free_block_alignment(anchor, block, align);
 
@@ -41,21 +70,22 @@ impl<const FLL: usize> Header<FLL> {
         #[cfg(any(fuzzing, test))]
         debug_assert!(block.usable_size() >= size);
 
+        self.track_used(block.usable_size());
         let alloc = block.into_used(anchor);
 
         #[cfg(any(fuzzing, test))]
         debug_assert_eq!(0, alloc.as_ptr() as usize % align as usize);
 
-        Some(alloc)
+        Ok(alloc)
     }
 
     unsafe fn adjust_free_block_alignment<'a>(
         &mut self,
         anchor: Anchor<'a>,
         block: FreeBlock<'a>,
-        align: u16,
+        align: Size,
     ) -> FreeBlock<'a> {
-        let align = usize::from(align);
+        let align = align as usize;
         let address = block.body_ptr().as_ptr() as usize;
         let rem = unsafe { address.checked_rem(align).unwrap_unchecked() };
 
@@ -65,12 +95,14 @@ impl<const FLL: usize> Header<FLL> {
                 #[cfg(all(test, not(miri)))]
                 cov_mark::hit!(alloc_adjust_align_is_lt_free_header_size);
 
-                at += align;
+                // with wide offsets, `align` can be smaller than the header
+                at += (usize::from(FreeBlock::HEADER_SIZE) - at).next_multiple_of(align);
             } else {
                 #[cfg(all(test, not(miri)))]
                 cov_mark::hit!(alloc_adjust_align_is_gte_free_header_size);
             }
             let new = unsafe { anchor.split(&block, at) };
+            let block = unsafe { self.coalesce_prev(anchor, block) };
             unsafe { self.push(anchor, block) }
             new
         } else {
@@ -79,16 +111,16 @@ impl<const FLL: usize> Header<FLL> {
     }
 }
 
-// in the worst case scenario the block will be already `align`-byte aligned
-// the alignment of usable part of the block will be off by 4 bytes
-// (UsedBlock::HEADER_SIZE)
-// the block can be split in 2 but the first block will have a total size of at
-// least 8 bytes (FreeBlock::HEADER_SIZE)
-fn worst_case_size(size: u16, align: u16) -> Option<u16> {
+// the usable part of the block is always `BLOCK_ALIGN`-byte aligned. in the worst case scenario it
+// is `BLOCK_ALIGN` bytes past an `align`-byte boundary: the block must then be split in 2 and,
+// because the first block must have a total size of at least `FreeBlock::HEADER_SIZE`, the split
+// happens `align + FreeBlock::HEADER_SIZE - BLOCK_ALIGN` bytes into the block
+pub(super) fn worst_case_size(size: Size, align: Size) -> Option<Size> {
     if align <= consts::BLOCK_ALIGN.into() {
         Some(size)
     } else {
-        align.checked_add(4)?.checked_add(size)
+        let padding = FreeBlock::HEADER_SIZE - consts::BLOCK_ALIGN;
+        align.checked_add(padding.into())?.checked_add(size)
     }
 }
 
@@ -98,12 +130,14 @@ mod tests {
 
     use super::*;
     use crate::block::UsedBlock;
+    use crate::consts::CANARY_SIZE as CANARY;
+    use crate::helpers::{pool_len, Aligned, MIN_ALLOC as MIN};
+
+    const TOTAL: usize = UsedBlock::HEADER_SIZE as usize + MIN as usize;
 
     #[test]
     fn worst_case_size() {
-        // implementation and tests are hard-coded to these values
-        assert_eq!(4, UsedBlock::HEADER_SIZE);
-        assert_eq!(8, FreeBlock::HEADER_SIZE);
+        const PADDING: Size = (FreeBlock::HEADER_SIZE - consts::BLOCK_ALIGN) as Size;
 
         // repr(packed)
         assert_eq!(Some(1), super::worst_case_size(1, 1));
@@ -111,40 +145,86 @@ mod tests {
         assert_eq!(Some(2), super::worst_case_size(2, 2));
         assert_eq!(Some(4), super::worst_case_size(4, 2));
 
-        assert_eq!(Some(4), super::worst_case_size(4, 4));
-        assert_eq!(Some(8), super::worst_case_size(8, 4));
+        const G: Size = consts::BLOCK_ALIGN as Size;
+        assert_eq!(Some(G), super::worst_case_size(G, G));
+        assert_eq!(Some(2 * G), super::worst_case_size(2 * G, G));
+
+        assert_eq!(Some(G + 2 * G + PADDING), super::worst_case_size(G, 2 * G));
+        assert_eq!(
+            Some(2 * G + 2 * G + PADDING),
+            super::worst_case_size(2 * G, 2 * G)
+        );
+        assert_eq!(
+            Some(4 * G + 2 * G + PADDING),
+            super::worst_case_size(4 * G, 2 * G)
+        );
+
+        assert_eq!(
+            Some(3 * G + 4 * G + PADDING),
+            super::worst_case_size(3 * G, 4 * G)
+        );
+        assert_eq!(
+            Some(4 * G + 4 * G + PADDING),
+            super::worst_case_size(4 * G, 4 * G)
+        );
+        assert_eq!(
+            Some(5 * G + 4 * G + PADDING),
+            super::worst_case_size(5 * G, 4 * G)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let layout = Layout::new::<u32>();
+        assert_eq!(
+            Some(AllocError::Uninitialized),
+            tlsf.try_memalign(layout).err()
+        );
 
-        assert_eq!(Some(16), super::worst_case_size(4, 8));
-        assert_eq!(Some(20), super::worst_case_size(8, 8));
-        assert_eq!(Some(28), super::worst_case_size(16, 8));
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
 
-        assert_eq!(Some(32), super::worst_case_size(12, 16));
-        assert_eq!(Some(36), super::worst_case_size(16, 16));
-        assert_eq!(Some(40), super::worst_case_size(20, 16));
+        let zero_size = Layout::new::<()>();
+        assert_eq!(
+            Some(AllocError::ZeroSize),
+            tlsf.try_memalign(zero_size).err()
+        );
+
+        let too_wide = Layout::from_size_align(Size::MAX as usize + 1, 4).unwrap();
+        assert_eq!(
+            Some(AllocError::UnsupportedLayout),
+            tlsf.try_memalign(too_wide).err()
+        );
+
+        // fits in `MAX_ALLOC_SIZE` but not once the worst case alignment padding is added
+        let align = 4 * usize::from(consts::BLOCK_ALIGN);
+        let padded = Layout::from_size_align(Header::<1>::MAX_ALLOC_SIZE as usize, align).unwrap();
+        assert_eq!(Some(AllocError::TooLarge), tlsf.try_memalign(padded).err());
     }
 
     #[test]
     fn no_split() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         tlsf.initialize(&mut memory);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(4, free.usable_size());
+        assert_eq!(MIN, free.usable_size());
 
         let alloc = tlsf.memalign(Layout::new::<u8>()).unwrap();
-        assert_eq!(1, alloc.len());
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
         assert!(tlsf.free_blocks().is_empty());
     }
 
     #[test]
     fn split_size() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         tlsf.initialize(&mut memory);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(16, free.total_size());
+        assert_eq!(2 * TOTAL, free.total_size());
 
         let alloc = {
             #[cfg(not(miri))]
@@ -153,78 +233,120 @@ mod tests {
             tlsf.memalign(Layout::new::<u8>())
         }
         .unwrap();
-        assert_eq!(1, alloc.len());
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
 
         let [remaining] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(8, remaining.total_size());
+        assert_eq!(TOTAL, remaining.total_size());
     }
 
     #[test]
     fn split_align_small() {
-        #[repr(align(8))]
+        #[repr(align(32))]
         struct Aligned<T>(T);
 
+        // the smallest alignment that memalign has to pad for
+        const ALIGN: usize = 2 * consts::BLOCK_ALIGN as usize;
+        const GRANULARITY: usize = consts::BLOCK_ALIGN as usize;
+        // the first split point that is at least `FreeBlock::HEADER_SIZE` bytes into the block and
+        // moves the body `GRANULARITY` bytes forward
+        const AT: usize =
+            (FreeBlock::HEADER_SIZE as usize - GRANULARITY).next_multiple_of(ALIGN) + GRANULARITY;
+        // words to skip so that the body of the free block is `GRANULARITY` bytes past an
+        // `ALIGN`-byte boundary
+        const SKIP: usize =
+            (ALIGN + GRANULARITY - (GRANULARITY + UsedBlock::HEADER_SIZE as usize) % ALIGN) % ALIGN
+                / 4;
+
+        let size = UsedBlock::HEADER_SIZE as usize
+            + super::worst_case_size(ALIGN as Size, ALIGN as Size).unwrap() as usize;
+
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = Aligned([MaybeUninit::uninit(); 8]);
-        tlsf.initialize(&mut memory.0[1..]);
+        let mut memory = Aligned([MaybeUninit::uninit(); 2 * ALIGN]);
+        tlsf.initialize(&mut memory.0[SKIP..][..(consts::BLOCK_ALIGN as usize + size) / 4]);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(24, free.total_size());
+        assert_eq!(size, free.total_size());
 
         let alloc = {
             #[cfg(not(miri))]
             cov_mark::check!(alloc_adjust_align_is_lt_free_header_size);
 
-            tlsf.memalign(Layout::new::<u64>())
+            // a block of `ALIGN` bytes once the canary is added
+            tlsf.memalign(Layout::from_size_align(ALIGN - CANARY as usize, ALIGN).unwrap())
         }
         .unwrap();
-        assert_eq!(2, alloc.len());
+        // the rest of the block is too small to be split off
+        assert_eq!(
+            size - AT - UsedBlock::HEADER_SIZE as usize - CANARY as usize,
+            alloc.len() * 4
+        );
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(12, free.total_size());
+        assert_eq!(AT, free.total_size());
     }
 
     #[test]
     fn split_align_large() {
-        #[repr(align(16))]
+        #[repr(align(64))]
         struct Aligned<T>(T);
 
-        let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = Aligned([MaybeUninit::uninit(); 11]);
-        tlsf.initialize(&mut memory.0[..]);
+        // leaves room for a free block in front of the aligned body
+        const ALIGN: usize = (2 * FreeBlock::HEADER_SIZE as usize).next_power_of_two();
+        const AT: usize = ALIGN - (consts::BLOCK_ALIGN + UsedBlock::HEADER_SIZE) as usize % ALIGN;
+        let size = UsedBlock::HEADER_SIZE as usize
+            + super::worst_case_size(ALIGN as Size, ALIGN as Size).unwrap() as usize;
+
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned([MaybeUninit::uninit(); 64]);
+        tlsf.initialize(&mut memory.0[..(consts::BLOCK_ALIGN as usize + size) / 4]);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(40, free.total_size());
+        assert_eq!(size, free.total_size());
 
         let alloc = {
             #[cfg(not(miri))]
             cov_mark::check!(alloc_adjust_align_is_gte_free_header_size);
 
-            tlsf.memalign(Layout::new::<Aligned<u8>>())
+            // a block of `ALIGN` bytes once the canary is added
+            tlsf.memalign(Layout::from_size_align(ALIGN - CANARY as usize, ALIGN).unwrap())
         }
         .unwrap();
-        assert_eq!(4, alloc.len());
+        assert_eq!((ALIGN - CANARY as usize) / 4, alloc.len());
 
         let [align_adjust, size_adjust] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(8, align_adjust.total_size());
-        assert_eq!(12, size_adjust.total_size());
+        assert_eq!(AT, align_adjust.total_size());
+        let rest = size - AT - UsedBlock::HEADER_SIZE as usize - ALIGN;
+        assert_eq!(rest, size_adjust.total_size());
     }
 
     #[test]
     fn odd_layout() {
+        #[repr(align(32))]
+        struct Aligned<T>(T);
+
+        // the smallest alignment that memalign has to pad for
+        const ALIGN: usize = 2 * consts::BLOCK_ALIGN as usize;
+        // words to skip so that the body of the free block is `ALIGN`-byte aligned
+        const SKIP: usize =
+            (ALIGN - (consts::BLOCK_ALIGN + UsedBlock::HEADER_SIZE) as usize % ALIGN) % ALIGN / 4;
+
+        let layout = Layout::from_size_align(1, ALIGN).unwrap();
+        // just large enough for `layout`
+        let size = UsedBlock::HEADER_SIZE as usize
+            + super::worst_case_size(MIN, ALIGN as Size).unwrap() as usize;
+
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 6];
-        tlsf.initialize(&mut memory);
+        let mut memory = Aligned([MaybeUninit::uninit(); 2 * ALIGN]);
+        tlsf.initialize(&mut memory.0[SKIP..][..(consts::BLOCK_ALIGN as usize + size) / 4]);
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(20, free.total_size());
+        assert_eq!(size, free.total_size());
 
-        let layout = Layout::from_size_align(1, 8).unwrap();
         let alloc = tlsf.memalign(layout).unwrap();
-        assert_eq!(1, alloc.len());
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
 
         let [free] = tlsf.free_blocks().try_into().unwrap();
-        assert_eq!(12, free.total_size());
+        assert_eq!(size - TOTAL, free.total_size());
     }
 
     #[cfg(not(miri))] // slow
@@ -239,7 +361,8 @@ mod tests {
         while let Some(alloc) = tlsf.memalign(Layout::new::<u8>()) {
             count += 1;
             assert!(!alloc.is_empty());
-            assert!(alloc.len() <= 2);
+            // the remainder of a block is only split off if it can hold a free block
+            assert!(alloc.len() * 4 < MIN as usize + usize::from(FreeBlock::HEADER_SIZE));
             alloc.iter_mut().for_each(|mu| {
                 mu.write(!0);
             });
@@ -248,4 +371,26 @@ mod tests {
         assert!(tlsf.free_blocks().is_empty());
         assert_eq!(count, tlsf.blocks().count());
     }
+
+    #[cfg(feature = "align-8")]
+    #[test]
+    fn naturally_aligned() {
+        #[repr(align(8))]
+        struct Aligned<T>(T);
+
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned([MaybeUninit::uninit(); 65]);
+        // misaligned on purpose
+        tlsf.initialize(&mut memory.0[1..]);
+
+        let layout = Layout::new::<u64>();
+        while let Some(alloc) = tlsf.memalign(layout) {
+            assert_eq!(0, alloc.as_ptr() as usize % layout.align());
+            // the whole block is handed out when the rest of it can't be split off
+            assert!(alloc.len() * 4 >= layout.size());
+
+            // no padding blocks are split off
+            assert!(tlsf.free_blocks().len() <= 1);
+        }
+    }
 }
@@ -1,19 +1,23 @@
 use crate::block::{Anchor, FreeBlock};
 use crate::header::Header;
-use crate::mapping;
 use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
 #[cfg(test)]
 use crate::Tlsf;
+use crate::{mapping, AllocError, Size};
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// # Safety
     /// - `header` must be associated to the given `anchor`
-    pub unsafe fn pop<'a>(&mut self, anchor: Anchor<'a>, size: u16) -> Option<FreeBlock<'a>> {
-        if size > Header::<FLL>::MAX_ALLOC_SIZE {
+    pub unsafe fn pop<'a>(&mut self, anchor: Anchor<'a>, size: Size) -> Option<FreeBlock<'a>> {
+        if size > Self::MAX_ALLOC_SIZE {
             return None;
         }
 
-        let guess = unsafe { Header::<FLL>::mapping_search(size) };
+        let guess = unsafe { Self::mapping_search(size) };
         let hit = unsafe { self.find_suitable_free_list(guess)? };
 
         let head = unsafe { self.get_free_list(hit.fl, hit.sl) };
@@ -32,11 +36,59 @@ impl<const FLL: usize> Header<FLL> {
         Some(block)
     }
 
+    /// Like `pop` but reports why no block was found
+    ///
+    /// `requested` is the size the caller needs; it can be smaller than `size` when `size` includes
+    /// alignment padding
+    ///
+    /// # Safety
+    /// - `header` must be associated to the given `anchor`
+    pub unsafe fn try_pop<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        size: Size,
+        requested: Size,
+    ) -> Result<FreeBlock<'a>, AllocError> {
+        if size > Self::MAX_ALLOC_SIZE {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(alloc_too_large);
+
+            return Err(AllocError::TooLarge);
+        }
+
+        let block = self.pop(anchor, size);
+
+        // the quarantine must not cause allocation failures
+        #[cfg(feature = "hardening")]
+        let block = match block {
+            None if self.drain_quarantine(anchor) => self.pop(anchor, size),
+            block => block,
+        };
+
+        block.ok_or_else(|| {
+            let free_bytes = self.stats().free_bytes;
+            if free_bytes >= requested as usize {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(alloc_fragmented);
+
+                AllocError::Fragmented { free_bytes }
+            } else {
+                AllocError::OutOfMemory { free_bytes }
+            }
+        })
+    }
+
+    /// Returns whether `pop` would find a block of `size` bytes
+    pub fn can_pop(&self, size: Size) -> bool {
+        size <= Self::MAX_ALLOC_SIZE
+            && unsafe { self.find_suitable_free_list(Self::mapping_search(size)) }.is_some()
+    }
+
     unsafe fn find_suitable_free_list(&self, guess: Indices) -> Option<Indices> {
         #[cfg(any(fuzzing, test))]
         debug_assert!(usize::from(guess.fl) < FLL);
         #[cfg(any(fuzzing, test))]
-        debug_assert!(guess.sl < crate::consts::SLL);
+        debug_assert!(usize::from(guess.sl) < SL);
 
         let suitable_sl = self.suitable_sls(guess.fl, guess.sl);
         let (fl, sl) = if suitable_sl != 0 {
@@ -65,9 +117,12 @@ impl<const FLL: usize> Header<FLL> {
 }
 
 #[cfg(test)]
-impl<'a, const FLL: usize> Tlsf<'a, FLL> {
-    fn pop_free(&mut self, size: u16) -> Option<FreeBlock<'a>> {
-        let anchor = self.anchor?;
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    fn pop_free(&mut self, size: Size) -> Option<FreeBlock<'a>> {
+        let anchor = self.anchor()?;
 
         unsafe { self.header.pop(anchor, size) }
     }
@@ -75,10 +130,9 @@ impl<'a, const FLL: usize> Tlsf<'a, FLL> {
 
 #[cfg(test)]
 mod tests {
-    use core::mem::MaybeUninit;
-
     use super::*;
-    use crate::helpers::FreeBlocks;
+    use crate::consts::MIN_USABLE_SIZE as MIN;
+    use crate::helpers::{pool_len, Aligned, FreeBlocks};
 
     #[test]
     fn when_no_free_blocks() {
@@ -93,11 +147,11 @@ mod tests {
     #[test]
     fn leaves_list_empty() {
         let mut tlsf = Tlsf::<1>::empty();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         tlsf.initialize(&mut memory);
 
         let block = tlsf.pop_free(0).unwrap();
-        assert_eq!(4, block.usable_size());
+        assert_eq!(MIN, block.usable_size());
         assert!(!tlsf.header.is_fl_bit_set(0));
         unsafe {
             assert!(tlsf.header.is_sl_empty(0));
@@ -107,14 +161,14 @@ mod tests {
     #[test]
     fn list_stays_nonempty() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first);
 
-            let second = free_blocks.next(4, true);
+            let second = free_blocks.next(MIN, true);
             header.push(anchor, second);
 
             let _block = header.pop(anchor, 0).unwrap();
@@ -125,73 +179,75 @@ mod tests {
             assert!(other.get_next_free().is_none());
 
             assert!(header.is_fl_bit_set(0));
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
         }
     }
 
     #[test]
     fn found_suitable_list_at_guess_fl() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 8];
+        let mut memory = Aligned::<{ pool_len(&[2 * MIN, 3 * MIN]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(8, false);
+            let first = free_blocks.next(2 * MIN, false);
             header.push(anchor, first);
 
-            let second = free_blocks.next(12, true);
+            let second = free_blocks.next(3 * MIN, true);
             header.push(anchor, second);
 
             let block = {
                 #[cfg(not(miri))]
                 cov_mark::check!(found_suitable_list_at_guess_fl);
 
-                header.pop(anchor, 4).unwrap()
+                header.pop(anchor, MIN).unwrap()
             };
 
-            assert_eq!(8, block.usable_size());
+            assert_eq!(2 * MIN, block.usable_size());
         }
     }
 
     #[test]
     fn found_suitable_list_at_higher_fl() {
+        const LARGE: Size = Header::<2>::LOWER_SIZE_THRESHOLD;
+
         let mut header = Header::<2>::new();
-        let mut memory = [MaybeUninit::uninit(); 20];
+        let mut memory = Aligned::<{ pool_len(&[MIN, LARGE]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first);
 
-            let second = free_blocks.next(64, true);
+            let second = free_blocks.next(LARGE, true);
             header.push(anchor, second);
 
             let block = {
                 #[cfg(not(miri))]
                 cov_mark::check!(found_suitable_list_at_higher_fl);
 
-                header.pop(anchor, 8).unwrap()
+                header.pop(anchor, 2 * MIN).unwrap()
             };
 
-            assert_eq!(64, block.usable_size());
+            assert_eq!(LARGE, block.usable_size());
         }
     }
 
     #[test]
     fn found_no_suitable_list() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 20];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let block = free_blocks.next(4, true);
+            let block = free_blocks.next(MIN, true);
             header.push(anchor, block);
 
             let res = {
                 #[cfg(not(miri))]
                 cov_mark::check!(found_no_suitable_list);
 
-                header.pop(anchor, 8)
+                header.pop(anchor, 2 * MIN)
             };
 
             assert!(res.is_none());
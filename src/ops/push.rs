@@ -1,12 +1,19 @@
 use crate::block::{Anchor, FreeBlock};
 use crate::header::Header;
 use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     /// # Safety
     /// - `block` must be covered by `anchor`
     pub(super) unsafe fn push<'a>(&mut self, anchor: Anchor<'a>, block: FreeBlock<'a>) {
-        let Indices { fl, sl } = Header::<FLL>::mapping_insert(block.usable_size());
+        #[cfg(feature = "hardening")]
+        block.poison(anchor);
+
+        let Indices { fl, sl } = Self::mapping_insert(block.usable_size());
 
         let free_list = unsafe { self.get_free_list(fl, sl) };
 
@@ -30,24 +37,25 @@ impl<const FLL: usize> Header<FLL> {
 
         self.set_fl_bit(fl);
         self.set_sl_bit(fl, sl);
+
+        self.track_free(block.usable_size());
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::mem::MaybeUninit;
-
     use super::*;
-    use crate::helpers::FreeBlocks;
+    use crate::consts::MIN_USABLE_SIZE as MIN;
+    use crate::helpers::{pool_len, Aligned, FreeBlocks};
 
     #[test]
     fn one() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let block = free_blocks.next(4, true);
+            let block = free_blocks.next(MIN, true);
 
             {
                 #[cfg(not(miri))]
@@ -60,24 +68,23 @@ mod tests {
         let blocks = unsafe { header.free_blocks(free_blocks.anchor) };
         assert_eq!(1, blocks.len());
         let [block] = blocks.try_into().unwrap();
-        assert_eq!(4, block.usable_size());
+        assert_eq!(MIN, block.usable_size());
+        assert_eq!(MIN as usize, header.stats().free_bytes);
         assert!(header.is_fl_bit_set(0));
-        unsafe {
-            assert!(header.is_sl_bit_set(0, 1));
-        }
+        assert!(header.is_list_set(MIN));
     }
 
     #[test]
     fn two_same_list() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first);
 
-            let second = free_blocks.next(4, true);
+            let second = free_blocks.next(MIN, true);
             {
                 #[cfg(not(miri))]
                 cov_mark::check!(push_non_empty_list);
@@ -98,32 +105,28 @@ mod tests {
         }
 
         assert!(header.is_fl_bit_set(0));
-        unsafe {
-            assert!(header.is_sl_bit_set(0, 1));
-        }
+        assert!(header.is_list_set(MIN));
     }
 
     #[test]
     fn two_different_lists() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 6];
+        let mut memory = Aligned::<{ pool_len(&[MIN, 2 * MIN]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, false);
+            let first = free_blocks.next(MIN, false);
             header.push(anchor, first);
 
-            let second = free_blocks.next(8, true);
+            let second = free_blocks.next(2 * MIN, true);
             header.push(anchor, second);
         }
 
         let blocks = unsafe { header.free_blocks(anchor) };
         assert_eq!(2, blocks.len());
-        assert_eq!(4, blocks[0].usable_size());
+        assert_eq!(MIN, blocks[0].usable_size());
         assert!(header.is_fl_bit_set(0));
-        unsafe {
-            assert!(header.is_sl_bit_set(0, 1));
-            assert!(header.is_sl_bit_set(0, 2));
-        }
+        assert!(header.is_list_set(MIN));
+        assert!(header.is_list_set(2 * MIN));
     }
 }
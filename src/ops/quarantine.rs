@@ -0,0 +1,342 @@
+use crate::block::{Anchor, Offset, UsedBlock};
+use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::{Corruption, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Holds up to `max_bytes` of freed memory in a quarantine before making it available again
+    ///
+    /// [`Tlsf::free`] poisons the block and appends it to a first-in first-out queue instead of
+    /// returning it to the free lists. When the queue holds more than `max_bytes` of usable size,
+    /// the oldest blocks are checked for writes to their bodies, reported as
+    /// [`Corruption::UseAfterFree`], and then actually freed. Blocks larger than `max_bytes` skip
+    /// the quarantine. An allocation request that finds no suitable free block drains the
+    /// quarantine before it fails.
+    ///
+    /// Quarantined blocks count as used blocks in [`Tlsf::stats`] and [`Tlsf::blocks`]. The queue
+    /// is threaded through the bodies of the blocks so it takes no extra memory.
+    ///
+    /// A `max_bytes` of `0`, the default, disables the quarantine
+    pub fn set_quarantine(&mut self, max_bytes: usize) {
+        self.header.quarantine_mut().limit = max_bytes;
+        if let Some(anchor) = self.anchor() {
+            unsafe { self.header.trim_quarantine(anchor) }
+        }
+    }
+
+    /// Returns the usable size, in bytes, of the blocks held in the quarantine
+    pub fn quarantined_bytes(&self) -> usize {
+        self.header.quarantine().bytes
+    }
+
+    /// Frees all the blocks held in the quarantine
+    pub fn drain_quarantine(&mut self) {
+        if let Some(anchor) = self.anchor() {
+            unsafe { self.header.drain_quarantine(anchor) };
+        }
+    }
+}
+
+/// First-in first-out queue of freed blocks that are not yet available for allocation
+///
+/// The blocks keep the "used" flag so they are neither coalesced nor handed out. Each block links
+/// to the next, newer, block through the `next_free` slot of its free block header
+pub(crate) struct Quarantine {
+    // oldest block
+    head: Option<Offset>,
+    // newest block
+    tail: Option<Offset>,
+    bytes: usize,
+    limit: usize,
+}
+
+impl Quarantine {
+    pub const EMPTY: Self = Self {
+        head: None,
+        tail: None,
+        bytes: 0,
+        limit: 0,
+    };
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Moves the freed `block` into the quarantine; returns `false` if the block must be freed
+    /// right away instead
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    pub(super) unsafe fn quarantine_block<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: &UsedBlock<'a>,
+    ) -> bool {
+        let size = block.usable_size() as usize;
+        if size > self.quarantine().limit {
+            return false;
+        }
+
+        block.quarantine(anchor);
+        let free = block.as_free_unchecked(anchor);
+        free.clear_next_free();
+
+        let offset = anchor.offset_of(block);
+        let quarantine = self.quarantine_mut();
+        match quarantine.tail {
+            Some(tail) => anchor.get_free_block(tail).set_next_free(offset),
+            None => quarantine.head = Some(offset),
+        }
+        quarantine.tail = Some(offset);
+        quarantine.bytes = quarantine.bytes.wrapping_add(size);
+
+        self.trim_quarantine(anchor);
+        true
+    }
+
+    /// Releases the oldest blocks until the quarantine fits in its limit
+    ///
+    /// # Safety
+    /// - `header` must be associated to the given `anchor`
+    pub(super) unsafe fn trim_quarantine(&mut self, anchor: Anchor<'_>) {
+        while self.quarantine().bytes > self.quarantine().limit {
+            if !self.release_quarantined(anchor) {
+                break;
+            }
+        }
+    }
+
+    /// Releases all the blocks in the quarantine; returns `false` if it was empty
+    ///
+    /// # Safety
+    /// - `header` must be associated to the given `anchor`
+    pub(super) unsafe fn drain_quarantine(&mut self, anchor: Anchor<'_>) -> bool {
+        let mut released = false;
+        while self.release_quarantined(anchor) {
+            released = true;
+        }
+        released
+    }
+
+    /// Frees the oldest block in the quarantine; returns `false` if the quarantine is empty
+    unsafe fn release_quarantined(&mut self, anchor: Anchor<'_>) -> bool {
+        let Some(offset) = self.quarantine().head else {
+            return false;
+        };
+
+        let block = anchor.used_block_at(offset);
+        let size = block.usable_size();
+        let next = block.as_free_unchecked(anchor).get_next_free();
+
+        #[cfg(feature = "safe-linking")]
+        let next = if self.check_quarantine_link(anchor, offset, next) {
+            next
+        } else {
+            // leaks the rest of the queue
+            self.quarantine_mut().bytes = size as usize;
+            None
+        };
+
+        let quarantine = self.quarantine_mut();
+        quarantine.head = next;
+        if next.is_none() {
+            quarantine.tail = None;
+        }
+        quarantine.bytes = quarantine.bytes.wrapping_sub(size as usize);
+
+        if !block.is_quarantine_poisoned(anchor) {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(quarantine_use_after_free);
+
+            self.report(Corruption::UseAfterFree {
+                block: offset.get(),
+            });
+        }
+
+        self.untrack_used(size);
+        let free = block.into_free(anchor);
+        self.coalesce(anchor, free);
+        true
+    }
+
+    /// Checks that the `next` link of the quarantined block at `offset` points at another
+    /// quarantined block; reports it otherwise
+    #[cfg(feature = "safe-linking")]
+    unsafe fn check_quarantine_link(
+        &self,
+        anchor: Anchor<'_>,
+        offset: Offset,
+        next: Option<Offset>,
+    ) -> bool {
+        // `Option::is_none_or` needs a newer toolchain
+        #[allow(clippy::unnecessary_map_or)]
+        let is_quarantined = next.map_or(true, |next| {
+            anchor.covers(next, UsedBlock::HEADER_SIZE.into()) && {
+                let block = anchor.block_at(next);
+                block.is_used()
+                    && anchor.covers(next, block.total_size())
+                    && anchor.used_block_at(next).is_quarantined(anchor)
+            }
+        });
+
+        if !is_quarantined {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(quarantine_forged_link);
+
+            self.report(Corruption::FreeLink {
+                block: offset.get(),
+            });
+        }
+        is_quarantined
+    }
+
+    /// Masks the links of the quarantined blocks with the secret of `new` instead of that of `old`
+    ///
+    /// # Safety
+    /// - `header` must be associated to the given anchors, which must only differ in their secret
+    #[cfg(feature = "safe-linking")]
+    pub(super) unsafe fn rekey_quarantine(&mut self, old: Anchor<'_>, new: Anchor<'_>) {
+        let mut link = self.quarantine().head;
+        while let Some(offset) = link {
+            let block = old.used_block_at(offset);
+            link = block.as_free_unchecked(old).get_next_free();
+            if !self.check_quarantine_link(old, offset, link) {
+                link = None;
+            }
+
+            let block = block.as_free_unchecked(new);
+            match link {
+                Some(next) => block.set_next_free(next),
+                None => block.clear_next_free(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::block::FreeBlock;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{pool_len, Aligned};
+    use crate::ops::hardening::tests::{record, reports};
+    use crate::{FreeError, Size};
+
+    // usable size of the blocks, with any block granularity; leaves room for poison past the links
+    const SIZE: Size = 4 * consts::BLOCK_ALIGN as Size;
+
+    #[test]
+    fn fifo() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        // holds two blocks but not three
+        tlsf.set_quarantine(2 * SIZE as usize);
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let first = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let second = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+
+        unsafe { tlsf.free(first) }
+        unsafe { tlsf.free(second) }
+        assert_eq!(2 * SIZE as usize, tlsf.quarantined_bytes());
+        assert_eq!(2, tlsf.stats().frees);
+        assert_eq!(3, tlsf.stats().used_blocks);
+
+        // the freed blocks are not reused right away
+        let third = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        assert!(third != first && third != second);
+
+        // releases `first` but not `second`
+        unsafe { tlsf.free(third) }
+        assert_eq!(2 * SIZE as usize, tlsf.quarantined_bytes());
+        let reused = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        assert_eq!(first, reused);
+
+        tlsf.drain_quarantine();
+        assert_eq!(0, tlsf.quarantined_bytes());
+        assert_eq!(2, tlsf.stats().used_blocks);
+        assert!(reports().is_empty());
+        assert_eq!(Ok(()), tlsf.check());
+    }
+
+    #[test]
+    fn use_after_free() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_quarantine(SIZE as usize);
+        let mut memory = Aligned::<{ pool_len(&[SIZE]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc((SIZE - CANARY).try_into().unwrap()).unwrap();
+        let block = tlsf.blocks().next().unwrap().offset();
+        let dangling = alloc.as_mut_ptr();
+        unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+
+        // writes past the quarantine link, which takes the place of the free list links
+        let links_len = (FreeBlock::HEADER_SIZE - UsedBlock::HEADER_SIZE) as usize / 4;
+        unsafe { dangling.add(links_len).write(MaybeUninit::new(0)) }
+
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(quarantine_use_after_free);
+            tlsf.set_quarantine(0);
+        }
+        assert_eq!([Corruption::UseAfterFree { block }], *reports());
+        assert_eq!(0, tlsf.stats().used_blocks);
+    }
+
+    #[test]
+    fn double_free() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_quarantine(SIZE as usize);
+        let mut memory = Aligned::<{ pool_len(&[SIZE]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let alloc = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let block = tlsf.blocks().next().unwrap().offset();
+        unsafe {
+            tlsf.free(alloc);
+            assert_eq!(Err(FreeError::DoubleFree), tlsf.try_free(alloc));
+            tlsf.free(alloc);
+        }
+        assert_eq!([Corruption::DoubleFree { block }], *reports());
+        assert_eq!(SIZE as usize, tlsf.quarantined_bytes());
+        assert_eq!(Ok(()), tlsf.check());
+    }
+
+    #[test]
+    fn drain_on_oom() {
+        let mut tlsf = Tlsf::<3>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_quarantine(4 * SIZE as usize);
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let mut allocs = vec![];
+        while let Some(alloc) = tlsf.malloc((SIZE - CANARY).try_into().unwrap()) {
+            allocs.push(alloc);
+        }
+        for alloc in allocs {
+            unsafe { tlsf.free(NonNull::from(alloc).cast()) }
+        }
+        assert_eq!(4 * SIZE as usize, tlsf.quarantined_bytes());
+
+        // only fits in the blocks of the quarantine once they're merged
+        let size = 4 * SIZE - CANARY;
+        assert!(tlsf.malloc(size.try_into().unwrap()).is_some());
+        assert_eq!(0, tlsf.quarantined_bytes());
+        assert!(reports().is_empty());
+    }
+}
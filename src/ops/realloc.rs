@@ -0,0 +1,271 @@
+use core::alloc::Layout;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+use crate::block::{Anchor, UsedBlock};
+use crate::header::Header;
+use crate::ops::util;
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, Tlsf};
+
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Resizes the block of memory behind `ptr` to make it compatible with `new_layout`
+    ///
+    /// The block is first resized in place: shrinking always happens in place and growing happens
+    /// in place when the next physical block is free and large enough. Otherwise, a new block is
+    /// allocated, the contents of the old block are copied into it and the old block is freed but
+    /// only if that involves copying no more than `max_copy` bytes.
+    ///
+    /// With a `max_copy` of `0` this operation executes in bounded constant time (`O(1)`): it
+    /// either resizes the block in place or fails.
+    ///
+    /// This function returns `None` when `new_layout` has a `size` equal to zero, when moving the
+    /// block would exceed the `max_copy` budget and when there's insufficient free memory to
+    /// satisfy the request. In all those cases the block behind `ptr` is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` MUST denote a block of memory currently allocated via this allocator
+    /// - `ptr` MUST not be used after this function returns `Some`; the returned block must be
+    ///   used instead
+    pub unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u32>,
+        new_layout: Layout,
+        max_copy: usize,
+    ) -> Option<&'a mut [MaybeUninit<u32>]> {
+        let anchor = self.anchor()?;
+        let usable_size = anchor.get_used_block(ptr)?.usable_size();
+        let (alloc, moved_from) = self.header.realloc(anchor, ptr, new_layout, max_copy)?;
+        // when the block was moved, both blocks are still allocated at this point
+        self.update_watermarks();
+        if let Some(used) = moved_from {
+            self.header.untrack_used(used.usable_size());
+            let free = used.into_free(anchor);
+            self.header.coalesce(anchor, free);
+        }
+        // the old block was (partially) freed
+        self.mark_freed_dirty(ptr, usable_size);
+        self.mark_alloc_dirty(alloc);
+        Some(alloc)
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Returns the resized allocation and, when the block had to be moved, the old block which is
+    /// left for the caller to free
+    unsafe fn realloc<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        ptr: NonNull<u32>,
+        new_layout: Layout,
+        max_copy: usize,
+    ) -> Option<(&'a mut [MaybeUninit<u32>], Option<UsedBlock<'a>>)> {
+        if new_layout.size() == 0 {
+            return None;
+        }
+
+        let size = util::round_up_block_size(new_layout.size().try_into().ok()?)?;
+
+        let block = anchor.get_used_block(ptr)?;
+        let usable_size = block.usable_size();
+
+        #[cfg(feature = "hardening")]
+        self.check_canary(anchor, &block);
+
+        // `align` is a power of 2
+        let is_aligned = (ptr.as_ptr() as usize) & (new_layout.align() - 1) == 0;
+        if is_aligned {
+            if size <= usable_size {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(realloc_shrink_in_place);
+
+                return Some((self.shrink_in_place(anchor, block, size), None));
+            }
+
+            if let Some(alloc) = self.grow_in_place(anchor, block, size) {
+                #[cfg(all(test, not(miri)))]
+                cov_mark::hit!(realloc_grow_in_place);
+
+                return Some((alloc, None));
+            }
+        }
+
+        // the canary is not part of the allocation; copying it could clobber the new canary
+        let copy_size = usable_size.min(size) - consts::CANARY_SIZE;
+        if copy_size as usize > max_copy {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(realloc_over_copy_budget);
+
+            return None;
+        }
+
+        let alloc = self.memalign(anchor, new_layout).ok()?;
+
+        #[cfg(all(test, not(miri)))]
+        cov_mark::hit!(realloc_copy);
+
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr().cast::<MaybeUninit<u32>>(),
+            alloc.as_mut_ptr(),
+            copy_size as usize / mem::size_of::<u32>(),
+        );
+
+        // `ptr` was already found to be covered by `anchor` above
+        Some((alloc, Some(anchor.get_used_block(ptr).unwrap_unchecked())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{pool_len, Aligned, MIN_ALLOC as MIN};
+    use crate::Size;
+
+    const TOTAL: usize = UsedBlock::HEADER_SIZE as usize + MIN as usize;
+
+    #[test]
+    fn grow_in_place() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc((MIN - CANARY).try_into().unwrap()).unwrap();
+        alloc[0].write(42);
+        let ptr = NonNull::from(alloc).cast();
+
+        let layout = Layout::from_size_align((2 * MIN - CANARY) as usize, 4).unwrap();
+        let alloc = unsafe {
+            #[cfg(not(miri))]
+            cov_mark::check!(realloc_grow_in_place);
+
+            tlsf.realloc(ptr, layout, 0)
+        }
+        .unwrap();
+        assert_eq!((2 * MIN - CANARY) as usize / 4, alloc.len());
+        assert_eq!(ptr, NonNull::from(&alloc[0]).cast());
+        assert_eq!(42, unsafe { alloc[0].assume_init() });
+
+        let [free] = tlsf.free_blocks().try_into().unwrap();
+        assert_eq!(
+            TOTAL + usize::from(UsedBlock::HEADER_SIZE),
+            free.total_size()
+        );
+    }
+
+    #[test]
+    fn shrink_in_place() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (3 * TOTAL - usize::from(UsedBlock::HEADER_SIZE)) as Size - CANARY;
+        let alloc = tlsf.malloc(size.try_into().unwrap()).unwrap();
+        alloc[0].write(42);
+        let ptr = NonNull::from(alloc).cast();
+        assert!(tlsf.free_blocks().is_empty());
+
+        let alloc = unsafe {
+            #[cfg(not(miri))]
+            cov_mark::check!(realloc_shrink_in_place);
+
+            tlsf.realloc(ptr, Layout::new::<u32>(), 0)
+        }
+        .unwrap();
+        assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
+        assert_eq!(ptr, NonNull::from(&alloc[0]).cast());
+        assert_eq!(42, unsafe { alloc[0].assume_init() });
+
+        let [free] = tlsf.free_blocks().try_into().unwrap();
+        assert_eq!(2 * TOTAL, free.total_size());
+    }
+
+    #[test]
+    fn copy_budget() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN, MIN, 2 * MIN]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (MIN - CANARY).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        first[0].write(42);
+        let ptr = NonNull::from(first).cast();
+
+        let second = tlsf.malloc(size).unwrap();
+        second[0].write(24);
+
+        let layout = Layout::from_size_align((2 * MIN - CANARY) as usize, 4).unwrap();
+        unsafe {
+            #[cfg(not(miri))]
+            cov_mark::check!(realloc_over_copy_budget);
+
+            assert!(tlsf.realloc(ptr, layout, 0).is_none());
+            assert!(tlsf
+                .realloc(ptr, layout, (MIN - CANARY) as usize - 1)
+                .is_none());
+        }
+
+        let alloc = unsafe {
+            #[cfg(not(miri))]
+            cov_mark::check!(realloc_copy);
+
+            tlsf.realloc(ptr, layout, (MIN - CANARY) as usize)
+        }
+        .unwrap();
+        assert_eq!((2 * MIN - CANARY) as usize / 4, alloc.len());
+        assert_ne!(ptr, NonNull::from(&alloc[0]).cast());
+        assert_eq!(42, unsafe { alloc[0].assume_init() });
+        assert_eq!(24, unsafe { second[0].assume_init() });
+
+        // the old block was returned to the allocator
+        let blocks = tlsf.blocks().collect::<Vec<_>>();
+        assert!(blocks[0].is_free());
+        assert_eq!(MIN, blocks[0].usable_size());
+    }
+
+    #[test]
+    fn misaligned() {
+        #[repr(align(32))]
+        struct Aligned<T>(T);
+
+        // the smallest alignment that the blocks don't already have
+        const ALIGN: usize = 2 * consts::BLOCK_ALIGN as usize;
+        // words to skip so that the body of the first block is not `ALIGN`-byte aligned
+        const SKIP: usize = (consts::BLOCK_ALIGN as usize + ALIGN
+            - (consts::BLOCK_ALIGN + UsedBlock::HEADER_SIZE) as usize % ALIGN)
+            % ALIGN
+            / 4;
+
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned([MaybeUninit::uninit(); 2 * ALIGN]);
+        tlsf.initialize(&mut memory.0[SKIP..]);
+
+        let alloc = tlsf.malloc((MIN - CANARY).try_into().unwrap()).unwrap();
+        let ptr = NonNull::from(alloc).cast::<u32>();
+        assert_ne!(0, ptr.as_ptr() as usize % ALIGN);
+
+        // would fit in place but the alignment requirement forces a move
+        let layout = Layout::from_size_align(ALIGN, ALIGN).unwrap();
+        let alloc = unsafe { tlsf.realloc(ptr, layout, usize::MAX) }.unwrap();
+        assert_eq!(0, alloc.as_ptr() as usize % ALIGN);
+    }
+
+    #[test]
+    fn zero_size() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc((MIN - CANARY).try_into().unwrap()).unwrap();
+        let layout = Layout::from_size_align(0, 1).unwrap();
+        let ptr = NonNull::from(alloc).cast();
+        assert!(unsafe { tlsf.realloc(ptr, layout, usize::MAX) }.is_none());
+    }
+}
@@ -0,0 +1,350 @@
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+use super::util;
+use crate::block::{Anchor, FreeBlock, UsedBlock};
+use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::{NonZeroSize, Size, Tlsf};
+
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Tries to grow the block of memory behind `ptr` to a usable size of at least `new_size`
+    /// bytes without moving it
+    ///
+    /// The block grows by absorbing the next physical block, which must be free and large enough.
+    /// This operation executes in bounded constant time (`O(1)`).
+    ///
+    /// This function returns `None`, leaving the block untouched, when the block can't be grown in
+    /// place
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` MUST denote a block of memory currently allocated via this allocator
+    /// - the block previously returned by the allocator MUST not be used after this function
+    ///   returns `Some`; the returned block, which starts at `ptr`, must be used instead
+    pub unsafe fn try_grow_in_place(
+        &mut self,
+        ptr: NonNull<u32>,
+        new_size: NonZeroSize,
+    ) -> Option<&'a mut [MaybeUninit<u32>]> {
+        let anchor = self.anchor()?;
+        let size = util::round_up_block_size(new_size.into())?;
+        let block = anchor.get_used_block(ptr)?;
+
+        #[cfg(feature = "hardening")]
+        self.header.check_canary(anchor, &block);
+
+        let alloc = self.header.grow_in_place(anchor, block, size)?;
+        self.update_watermarks();
+        self.mark_alloc_dirty(alloc);
+        Some(alloc)
+    }
+
+    /// Shrinks the block of memory behind `ptr` to a usable size of at least `new_size` bytes
+    /// without moving it
+    ///
+    /// The tail of the block is returned to the allocator when it's large enough to become a block
+    /// on its own. This operation executes in bounded constant time (`O(1)`).
+    ///
+    /// This function returns `None`, leaving the block untouched, when `new_size` is greater than
+    /// the usable size of the block
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` MUST denote a block of memory currently allocated via this allocator
+    /// - the block previously returned by the allocator MUST not be used after this function
+    ///   returns `Some`; the returned block, which starts at `ptr`, must be used instead
+    pub unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u32>,
+        new_size: NonZeroSize,
+    ) -> Option<&'a mut [MaybeUninit<u32>]> {
+        let anchor = self.anchor()?;
+        let size = util::round_up_block_size(new_size.into())?;
+        let block = anchor.get_used_block(ptr)?;
+        let usable_size = block.usable_size();
+        if size > usable_size {
+            return None;
+        }
+
+        #[cfg(feature = "hardening")]
+        self.header.check_canary(anchor, &block);
+
+        self.mark_freed_dirty(ptr, usable_size);
+        Some(self.header.shrink_in_place(anchor, block, size))
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Grows `block` to a usable size of at least `size` bytes by absorbing the next physical
+    /// block
+    ///
+    /// Returns `None`, leaving `block` untouched, when the next physical block is not free or is
+    /// not large enough
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    /// - `size` must be a multiple of `BLOCK_ALIGN`
+    pub(super) unsafe fn grow_in_place<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: UsedBlock<'a>,
+        size: Size,
+    ) -> Option<&'a mut [MaybeUninit<u32>]> {
+        let block = block.as_free_unchecked(anchor);
+        if size <= block.usable_size() {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(grow_in_place_already_large_enough);
+
+            return Some(block.into_used(anchor));
+        }
+
+        let next = anchor
+            .next_phys_block(&block)
+            .and_then(|next| next.try_into_free(anchor))?;
+
+        let new_usable_size = (block.usable_size() as usize).wrapping_add(next.total_size());
+        if new_usable_size < size as usize || Size::try_from(new_usable_size).is_err() {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(grow_in_place_next_too_small);
+
+            return None;
+        }
+
+        self.untrack_used(block.usable_size());
+        self.unlink(anchor, &next);
+        block.merge(anchor, &next);
+
+        let block = self.adjust_free_block_size(anchor, block, size);
+
+        self.track_used(block.usable_size());
+        Some(block.into_used(anchor))
+    }
+
+    /// Shrinks `block` to a usable size of `size` bytes
+    ///
+    /// The tail of the block is returned to the allocator, and coalesced with the next physical
+    /// block, only when it's large enough to become a free block on its own
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    /// - `size` must be a multiple of `BLOCK_ALIGN`
+    /// - `size` must not exceed the usable size of `block`
+    pub(super) unsafe fn shrink_in_place<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: UsedBlock<'a>,
+        size: Size,
+    ) -> &'a mut [MaybeUninit<u32>] {
+        #[cfg(any(fuzzing, test))]
+        debug_assert!(size <= block.usable_size());
+
+        let block = block.as_free_unchecked(anchor);
+
+        if u64::from(block.usable_size()) >= u64::from(size) + u64::from(FreeBlock::HEADER_SIZE) {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(shrink_in_place_split);
+
+            self.untrack_used(block.usable_size());
+            let at = usize::from(UsedBlock::HEADER_SIZE) + size as usize;
+            let tail = anchor.split(&block, at);
+            self.coalesce(anchor, tail);
+            self.track_used(block.usable_size());
+        }
+
+        block.into_used(anchor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{CANARY_SIZE as CANARY, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::{pool_len, Aligned, FreeBlocks, MIN_ALLOC};
+
+    const TOTAL: Size = UsedBlock::HEADER_SIZE as Size + MIN;
+    // fills a pool that holds three blocks of the smallest size
+    const FULL: Size = MIN + 2 * TOTAL;
+
+    #[test]
+    fn try_grow() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[FULL]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf
+            .malloc((MIN_ALLOC - CANARY).try_into().unwrap())
+            .unwrap();
+        alloc[0].write(42);
+        let ptr = NonNull::from(alloc).cast();
+
+        let size = (FULL - CANARY).try_into().unwrap();
+        let alloc = unsafe { tlsf.try_grow_in_place(ptr, size) }.unwrap();
+        assert_eq!((FULL - CANARY) as usize / 4, alloc.len());
+        assert_eq!(ptr, NonNull::from(&alloc[0]).cast());
+        assert_eq!(42, unsafe { alloc[0].assume_init() });
+        assert!(tlsf.free_blocks().is_empty());
+
+        // no more room to grow
+        let size = (FULL + MIN - CANARY).try_into().unwrap();
+        assert!(unsafe { tlsf.try_grow_in_place(ptr, size) }.is_none());
+
+        let alloc = {
+            #[cfg(not(miri))]
+            cov_mark::check!(grow_in_place_already_large_enough);
+
+            let size = (FULL - MIN - CANARY).try_into().unwrap();
+            unsafe { tlsf.try_grow_in_place(ptr, size) }
+        }
+        .unwrap();
+        assert_eq!((FULL - CANARY) as usize / 4, alloc.len());
+    }
+
+    #[test]
+    fn shrink() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[FULL]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc((FULL - CANARY).try_into().unwrap()).unwrap();
+        alloc[0].write(42);
+        let ptr = NonNull::from(alloc).cast();
+
+        let size = (FULL + MIN - CANARY).try_into().unwrap();
+        assert!(unsafe { tlsf.shrink_in_place(ptr, size) }.is_none());
+
+        let alloc = unsafe { tlsf.shrink_in_place(ptr, 1.try_into().unwrap()) }.unwrap();
+        assert_eq!((MIN_ALLOC - CANARY) as usize / 4, alloc.len());
+        assert_eq!(ptr, NonNull::from(&alloc[0]).cast());
+        assert_eq!(42, unsafe { alloc[0].assume_init() });
+
+        let [free] = tlsf.free_blocks().try_into().unwrap();
+        assert_eq!((FULL - MIN_ALLOC) as usize, free.total_size());
+    }
+
+    #[test]
+    fn grow_absorbs_next() {
+        let mut header = Header::<1>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN, MIN + TOTAL]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            header.track_used(first.usable_size());
+            let alloc = first.into_used(anchor);
+
+            let second = free_blocks.next(MIN + TOTAL, true);
+            header.push(anchor, second);
+
+            let used = anchor.get_used_block(NonNull::from(alloc).cast()).unwrap();
+            let alloc = header.grow_in_place(anchor, used, 2 * MIN).unwrap();
+            assert_eq!((2 * MIN - CANARY) as usize / 4, alloc.len());
+
+            // the excess was split off and returned to the allocator
+            let [free] = header.free_blocks(anchor).try_into().unwrap();
+            assert_eq!(
+                (TOTAL + UsedBlock::HEADER_SIZE as Size) as usize,
+                free.total_size()
+            );
+        }
+    }
+
+    #[test]
+    fn grow_next_is_used() {
+        let mut header = Header::<1>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let alloc = first.into_used(anchor);
+
+            let second = free_blocks.next(MIN, true);
+            second.into_used(anchor);
+
+            let used = anchor.get_used_block(NonNull::from(alloc).cast()).unwrap();
+            assert!(header.grow_in_place(anchor, used, 2 * MIN).is_none());
+        }
+    }
+
+    #[test]
+    fn grow_next_too_small() {
+        let mut header = Header::<1>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN, false);
+            let alloc = first.into_used(anchor);
+
+            let second = free_blocks.next(MIN, true);
+            header.push(anchor, second);
+
+            let used = anchor.get_used_block(NonNull::from(alloc).cast()).unwrap();
+            let res = {
+                #[cfg(not(miri))]
+                cov_mark::check!(grow_in_place_next_too_small);
+
+                header.grow_in_place(anchor, used, 2 * TOTAL)
+            };
+            assert!(res.is_none());
+
+            // the free block was left untouched
+            let [free] = header.free_blocks(anchor).try_into().unwrap();
+            assert_eq!(MIN, free.usable_size());
+            assert!(header.is_list_set(MIN));
+        }
+    }
+
+    #[test]
+    fn shrink_coalesces_tail() {
+        let mut header = Header::<1>::new();
+        let mut memory = Aligned::<{ pool_len(&[MIN + TOTAL; 2]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(MIN + TOTAL, false);
+            header.track_used(first.usable_size());
+            let alloc = first.into_used(anchor);
+
+            let second = free_blocks.next(MIN + TOTAL, true);
+            header.push(anchor, second);
+
+            let used = anchor.get_used_block(NonNull::from(alloc).cast()).unwrap();
+            let alloc = {
+                #[cfg(not(miri))]
+                cov_mark::check!(shrink_in_place_split);
+
+                header.shrink_in_place(anchor, used, MIN)
+            };
+            assert_eq!((MIN - CANARY) as usize / 4, alloc.len());
+
+            let [free] = header.free_blocks(anchor).try_into().unwrap();
+            assert_eq!(3 * TOTAL as usize, free.total_size());
+            assert!(free.is_last_phys_block());
+        }
+    }
+
+    #[test]
+    fn shrink_tail_too_small() {
+        let mut header = Header::<1>::new();
+        let mut memory = Aligned::<{ pool_len(&[2 * MIN]) }>::new();
+        let mut free_blocks = FreeBlocks::new(&mut memory);
+        let anchor = free_blocks.anchor;
+        unsafe {
+            let first = free_blocks.next(2 * MIN, true);
+            let alloc = first.into_used(anchor);
+
+            let used = anchor.get_used_block(NonNull::from(alloc).cast()).unwrap();
+            let alloc = header.shrink_in_place(anchor, used, MIN);
+            assert_eq!((2 * MIN - CANARY) as usize / 4, alloc.len());
+            assert!(header.free_blocks(anchor).is_empty());
+        }
+    }
+}
@@ -0,0 +1,390 @@
+#[cfg(feature = "header-checksum")]
+use crate::block::UsedBlock;
+use crate::block::{Anchor, FreeBlock, Offset};
+use crate::header::Header;
+use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
+use crate::{CheckedBlocks, Corruption, Tlsf};
+
+impl<const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'_, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Sets the secret that protects the free list links, and the block headers, from heap
+    /// overflows
+    ///
+    /// Free blocks keep the links of their free list in their first bytes, where a buffer overrun
+    /// out of the preceding allocation can reach them. The links are stored XORed with a key made
+    /// of the secret and the address of the link. Before a block leaves its free list, or the
+    /// quarantine (see [`Tlsf::set_quarantine`]), the allocator checks that the blocks it links to
+    /// are free, or quarantined, and link back to it. On a mismatch, it calls the hook set with
+    /// [`Tlsf::set_corruption_hook`] with [`Corruption::FreeLink`] and drops the rest of the list
+    /// instead of following the link; the blocks in it are leaked and no longer count as free
+    /// memory in [`Tlsf::stats`].
+    ///
+    /// With the `header-checksum` feature, each block header also holds a checksum keyed with the
+    /// secret. [`Tlsf::free`] reports a header that does not match its checksum as
+    /// [`Corruption::HeaderChecksum`] and leaks the block instead of freeing it.
+    ///
+    /// The secret should come from a source of randomness, e.g. a hardware RNG; only as many of
+    /// its low bits as fit in a compressed offset mask the links. Changing the secret of an
+    /// allocator that manages memory updates all the links and checksums, which takes linear time
+    /// (`O(n)`).
+    ///
+    /// The default secret is `0`
+    pub fn set_secret(&mut self, secret: u32) {
+        let old = self.anchor();
+        self.header.set_secret(secret);
+        if let (Some(old), Some(new)) = (old, self.anchor()) {
+            unsafe { self.header.rekey(old, new) }
+        }
+    }
+}
+
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Checks that the neighbors of `block` in free list (`fl`, `sl`) link back to it; on a
+    /// mismatch, reports it and empties the free list
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    pub(super) unsafe fn check_links<'a>(
+        &mut self,
+        anchor: Anchor<'a>,
+        block: &FreeBlock<'a>,
+        fl: u8,
+        sl: u8,
+    ) -> bool {
+        let offset = anchor.offset_of(block);
+        let prev_agrees = match block.get_prev_free() {
+            None => self.get_free_list(fl, sl) == Some(offset),
+            Some(prev) => Self::linked_free_block(anchor, prev)
+                .is_some_and(|prev| prev.get_next_free() == Some(offset)),
+        };
+        // `Option::is_none_or` needs a newer toolchain
+        #[allow(clippy::unnecessary_map_or)]
+        let next_agrees = block.get_next_free().map_or(true, |next| {
+            Self::linked_free_block(anchor, next)
+                .is_some_and(|next| next.get_prev_free() == Some(offset))
+        });
+
+        if prev_agrees && next_agrees {
+            return true;
+        }
+
+        #[cfg(all(test, not(miri)))]
+        cov_mark::hit!(safe_linking_forged_link);
+
+        self.report(Corruption::FreeLink {
+            block: offset.get(),
+        });
+        self.clear_free_list(fl, sl);
+        self.untrack_leaked(anchor, offset, Indices { fl, sl });
+        false
+    }
+
+    /// Stops counting the free blocks of the dropped free list at `indices` as free memory, except
+    /// the block at `offset` that is leaving the list
+    ///
+    /// The links of the list can't be trusted so this walks the blocks of all pools instead
+    unsafe fn untrack_leaked(&mut self, anchor: Anchor<'_>, offset: Offset, indices: Indices) {
+        for block in CheckedBlocks::new(anchor) {
+            let Ok(block) = block else { break };
+            if block.is_free()
+                && block.raw_offset() != offset
+                && Self::mapping_insert(block.usable_size()) == indices
+            {
+                self.untrack_free(block.usable_size());
+            }
+        }
+    }
+
+    /// Reports a used block whose header does not match its checksum; such a block must not be
+    /// freed
+    ///
+    /// # Safety
+    /// - `block` must be covered by `anchor`
+    #[cfg(feature = "header-checksum")]
+    pub(super) unsafe fn check_checksum<'a>(
+        &self,
+        anchor: Anchor<'a>,
+        block: &UsedBlock<'a>,
+    ) -> bool {
+        let is_sealed = block.is_sealed(anchor.secret());
+        if !is_sealed {
+            #[cfg(all(test, not(miri)))]
+            cov_mark::hit!(header_checksum_mismatch);
+
+            self.report(Corruption::HeaderChecksum {
+                block: anchor.offset_of(block).get(),
+            });
+        }
+        is_sealed
+    }
+
+    /// Masks the links of the free, and quarantined, blocks with the secret of `new`, and reseals
+    /// the block headers, after the secret changed from that of `old`
+    ///
+    /// # Safety
+    /// - `header` must be associated to the given anchors, which must only differ in their secret
+    unsafe fn rekey(&mut self, old: Anchor<'_>, new: Anchor<'_>) {
+        // bounds the walk in case the lists were damaged into a cycle
+        let mut budget = self.stats().free_blocks;
+        for fl in 0..FLL as u8 {
+            for sl in 0..SL as u8 {
+                let mut link = self.get_free_list(fl, sl);
+                while let Some(offset) = link {
+                    let Some(block) = Self::linked_free_block(old, offset) else {
+                        break;
+                    };
+                    if budget == 0 {
+                        break;
+                    }
+                    budget = budget.wrapping_sub(1);
+
+                    link = block.get_next_free();
+                    relink(&new.get_free_block(offset), block.get_prev_free(), link);
+                }
+            }
+        }
+
+        self.rekey_quarantine(old, new);
+
+        #[cfg(feature = "header-checksum")]
+        for block in CheckedBlocks::new(new) {
+            let Ok(block) = block else { break };
+            block.seal();
+        }
+    }
+}
+
+/// Stores the given links in `block`
+fn relink(block: &FreeBlock<'_>, prev: Option<Offset>, next: Option<Offset>) {
+    match prev {
+        Some(prev) => block.set_prev_free(prev),
+        None => block.clear_prev_free(),
+    }
+    match next {
+        Some(next) => block.set_next_free(next),
+        None => block.clear_next_free(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::block::UsedBlock;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{pool_len, Aligned};
+    use crate::ops::hardening::tests::{record, reports};
+    use crate::Size;
+
+    // usable size of the blocks, with any block granularity
+    const SIZE: Size = 4 * consts::BLOCK_ALIGN as Size;
+
+    #[test]
+    fn masked() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_secret(0x5eed_c0de);
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let first = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let second = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let first_offset = tlsf.blocks().next().unwrap().offset();
+
+        unsafe {
+            tlsf.free(first);
+            tlsf.free(second);
+
+            // `second` heads the free list and links to `first`
+            let next_free = second.cast::<u16>().read();
+            assert_ne!(first_offset, usize::from(next_free));
+        }
+        assert_eq!(Ok(()), tlsf.check());
+
+        let reused = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        assert_eq!(second, reused);
+        let reused = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        assert_eq!(first, reused);
+        assert!(reports().is_empty());
+    }
+
+    #[test]
+    fn forged_link() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_secret(0x5eed_c0de);
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let size = 8.try_into().unwrap();
+        let overrun = tlsf.malloc(size).unwrap().as_mut_ptr().cast::<u8>();
+        let victim = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let mut blocks = tlsf.blocks();
+        let (target, target_size) = blocks
+            .next()
+            .map(|block| (block.offset(), block.usable_size()))
+            .unwrap();
+        let block = blocks.next().unwrap().offset();
+        unsafe { tlsf.free(victim) }
+
+        // points the `next_free` link of `victim` at the allocation that overruns into it
+        unsafe {
+            let header = overrun.add(target_size as usize);
+            let next_free = header.add(UsedBlock::HEADER_SIZE.into()).cast::<u16>();
+            next_free.write(target as u16);
+        }
+
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(safe_linking_forged_link);
+            let reused = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+            assert_eq!(victim, reused);
+        }
+        assert_eq!([Corruption::FreeLink { block }], *reports());
+        assert_eq!(Ok(()), tlsf.check());
+    }
+
+    #[test]
+    fn forged_link_leaks_list() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_secret(0x5eed_c0de);
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let overrun = tlsf.malloc(size).unwrap().as_mut_ptr().cast::<u8>();
+        let victim = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let leaked = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let target = tlsf.blocks().next().unwrap().offset();
+
+        // `victim` heads the free list and links to `leaked`
+        unsafe {
+            tlsf.free(leaked);
+            tlsf.free(victim);
+        }
+        assert_eq!(2, tlsf.stats().free_blocks);
+
+        // points the `next_free` link of `victim` at the allocation that overruns into it
+        unsafe {
+            let header = overrun.add(SIZE as usize);
+            let next_free = header.add(UsedBlock::HEADER_SIZE.into()).cast::<u16>();
+            next_free.write(target as u16);
+        }
+
+        let reused = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        assert_eq!(victim, reused);
+        assert_eq!(1, reports().len());
+
+        // `leaked` can't be reached anymore
+        let stats = tlsf.stats();
+        assert_eq!(0, stats.free_blocks);
+        assert_eq!(0, stats.free_bytes);
+        assert!(tlsf.malloc(size).is_none());
+    }
+
+    #[test]
+    fn forged_quarantine_link() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_secret(0x5eed_c0de);
+        tlsf.set_quarantine(64);
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let size = 8.try_into().unwrap();
+        let alloc = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let mut blocks = tlsf.blocks();
+        let block = blocks.next().unwrap().offset();
+        let guard = blocks.next().unwrap().offset();
+        unsafe { tlsf.free(alloc) }
+
+        // points the quarantine link at a block that is in use
+        unsafe { alloc.cast::<u16>().write(guard as u16) }
+
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(quarantine_forged_link);
+            tlsf.drain_quarantine();
+        }
+        assert_eq!([Corruption::FreeLink { block }], *reports());
+        assert_eq!(0, tlsf.quarantined_bytes());
+        assert_eq!(Ok(()), tlsf.check());
+    }
+
+    #[test]
+    fn rekey() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        // holds one block
+        tlsf.set_quarantine(SIZE as usize);
+        let mut memory = Aligned::<{ pool_len(&[SIZE; 4]) }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = (SIZE - CANARY).try_into().unwrap();
+        let first = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+        let second = NonNull::from(tlsf.malloc(size).unwrap()).cast::<u32>();
+        let _guard = tlsf.malloc(size).unwrap();
+
+        // `first` leaves the quarantine when `second` enters it
+        unsafe {
+            tlsf.free(first);
+            tlsf.free(second);
+        }
+        assert_eq!(SIZE as usize, tlsf.quarantined_bytes());
+
+        tlsf.set_secret(0x5eed_c0de);
+        assert_eq!(Ok(()), tlsf.check());
+        tlsf.drain_quarantine();
+        assert_eq!(Ok(()), tlsf.check());
+        assert!(tlsf.malloc(size).is_some());
+        assert!(reports().is_empty());
+    }
+
+    #[cfg(feature = "header-checksum")]
+    #[test]
+    fn header_checksum() {
+        let mut tlsf = Tlsf::<2>::empty();
+        tlsf.set_corruption_hook(record);
+        tlsf.set_secret(0x5eed_c0de);
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let alloc = NonNull::from(tlsf.malloc(8.try_into().unwrap()).unwrap()).cast::<u32>();
+        let block = tlsf.blocks().next().unwrap().offset();
+        let _guard = tlsf.malloc(8.try_into().unwrap()).unwrap();
+
+        // grows the block over `_guard`
+        unsafe {
+            let header = alloc
+                .as_ptr()
+                .cast::<u8>()
+                .sub(UsedBlock::HEADER_SIZE.into());
+            let size_free_last = header.cast::<crate::Size>();
+            size_free_last.write(size_free_last.read() + 16);
+        }
+
+        {
+            #[cfg(not(miri))]
+            cov_mark::check!(header_checksum_mismatch);
+            unsafe { tlsf.free(alloc) }
+        }
+        assert_eq!([Corruption::HeaderChecksum { block }], *reports());
+        assert_eq!(2, tlsf.stats().used_blocks);
+    }
+}
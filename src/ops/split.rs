@@ -1,4 +1,5 @@
 use crate::block::{Anchor, FreeBlock, UsedBlock};
+use crate::Size;
 
 impl<'a> Anchor<'a> {
     /// # Safety
@@ -6,7 +7,7 @@ impl<'a> Anchor<'a> {
     /// - block must be associated to `anchor`
     pub(super) unsafe fn split(&self, block: &FreeBlock<'a>, at: usize) -> FreeBlock<'a> {
         #[cfg(any(fuzzing, test))]
-        debug_assert_eq!(0, at % 4);
+        debug_assert_eq!(0, at % usize::from(crate::consts::BLOCK_ALIGN));
 
         #[cfg(any(fuzzing, test))]
         debug_assert!(at >= FreeBlock::HEADER_SIZE.into());
@@ -18,7 +19,7 @@ impl<'a> Anchor<'a> {
 
         let used_header_size = usize::from(UsedBlock::HEADER_SIZE);
         let is_last_phys_block = block.is_last_phys_block();
-        block.resize(at.wrapping_sub(used_header_size) as u16);
+        block.resize(at.wrapping_sub(used_header_size) as Size);
 
         let this_offset = self.offset_of(block);
         let new_offset = this_offset.add(at);
@@ -26,7 +27,7 @@ impl<'a> Anchor<'a> {
         let prev_phys_block = Some(self.offset_of(block));
         let new_block = self.create_free_block(
             new_offset,
-            total_size.wrapping_sub(at).wrapping_sub(used_header_size) as u16,
+            total_size.wrapping_sub(at).wrapping_sub(used_header_size) as Size,
             is_last_phys_block,
             prev_phys_block,
         );
@@ -53,24 +54,26 @@ impl<'a> Anchor<'a> {
 
 #[cfg(test)]
 mod tests {
-    use core::mem::MaybeUninit;
-
+    use super::*;
     use crate::block::Offset;
-    use crate::helpers::FreeBlocks;
+    use crate::consts::{self, MIN_USABLE_SIZE as MIN};
+    use crate::helpers::{pool_len, Aligned, FreeBlocks};
+
+    const TOTAL: Size = UsedBlock::HEADER_SIZE as Size + MIN;
 
     #[test]
     fn it_works() {
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
 
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let offset = Offset::compress(4);
-            let block = free_blocks.next(12, true);
-            let new = anchor.split(&block, 8);
+            let offset = Offset::compress(consts::BLOCK_ALIGN.into());
+            let block = free_blocks.next(MIN + TOTAL, true);
+            let new = anchor.split(&block, TOTAL as usize);
 
-            assert_eq!(4, block.usable_size());
-            assert_eq!(4, new.usable_size());
+            assert_eq!(MIN, block.usable_size());
+            assert_eq!(MIN, new.usable_size());
 
             assert_eq!(Some(offset), new.get_prev_phys_block());
         }
@@ -78,17 +81,17 @@ mod tests {
 
     #[test]
     fn last_phys_block() {
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
 
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let block = free_blocks.next(12, true);
+            let block = free_blocks.next(MIN + TOTAL, true);
             let new = {
                 #[cfg(not(miri))]
                 cov_mark::check!(split_not_last_phys_block);
 
-                anchor.split(&block, 8)
+                anchor.split(&block, TOTAL as usize)
             };
 
             assert!(new.is_last_phys_block());
@@ -98,21 +101,21 @@ mod tests {
 
     #[test]
     fn not_last_phys_block() {
-        let mut memory = [MaybeUninit::uninit(); 7];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
 
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(20, true);
+            let first = free_blocks.next(MIN + 2 * TOTAL, true);
 
-            let last = anchor.split(&first, 16);
+            let last = anchor.split(&first, 2 * TOTAL as usize);
             assert!(!first.is_last_phys_block());
 
             let mid = {
                 #[cfg(not(miri))]
                 cov_mark::check!(split_last_phys_block);
 
-                anchor.split(&first, 8)
+                anchor.split(&first, TOTAL as usize)
             };
             assert_eq!(Some(anchor.offset_of(&mid)), last.get_prev_phys_block());
         }
@@ -0,0 +1,242 @@
+use crate::sl::{SecondLevel, Sl};
+use crate::{AllocError, Stats, Tlsf, Watermarks};
+
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Returns the usage statistics of the allocator
+    ///
+    /// This operation executes in constant time (`O(1)`)
+    pub fn stats(&self) -> Stats {
+        self.header.stats()
+    }
+
+    /// Returns the peak memory usage of the allocator
+    ///
+    /// This operation executes in constant time (`O(1)`)
+    pub fn watermarks(&self) -> Watermarks {
+        self.watermarks
+    }
+
+    /// Resets the watermarks to the current memory usage of the allocator
+    ///
+    /// This operation executes in constant time (`O(1)`)
+    pub fn reset_watermarks(&mut self) {
+        let stats = self.header.stats();
+        self.watermarks = Watermarks {
+            peak_used_bytes: stats.used_bytes,
+            min_free_bytes: stats.free_bytes,
+            peak_used_blocks: stats.used_blocks,
+        };
+    }
+
+    /// Counts the outcome of an allocation request
+    pub(super) fn count_alloc<T>(&mut self, res: Result<T, AllocError>) -> Result<T, AllocError> {
+        if res.is_ok() {
+            self.header.count_alloc();
+            self.update_watermarks();
+        } else {
+            self.header.count_failed_alloc();
+        }
+        res
+    }
+
+    /// Updates the watermarks after memory was handed out
+    ///
+    /// Freeing memory can't move the watermarks so there's no need to call this from `free`
+    pub(super) fn update_watermarks(&mut self) {
+        let stats = self.header.stats();
+        let marks = &mut self.watermarks;
+        marks.peak_used_bytes = marks.peak_used_bytes.max(stats.used_bytes);
+        marks.min_free_bytes = marks.min_free_bytes.min(stats.free_bytes);
+        marks.peak_used_blocks = marks.peak_used_blocks.max(stats.used_blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+    use core::mem::MaybeUninit;
+    use core::ptr::NonNull;
+
+    use super::*;
+    use crate::helpers::{pool_len, Aligned};
+    use crate::{consts, Size};
+
+    fn check<const FLL: usize>(tlsf: &Tlsf<'_, FLL>) {
+        let mut expected = Stats {
+            allocs: tlsf.stats().allocs,
+            frees: tlsf.stats().frees,
+            failed_allocs: tlsf.stats().failed_allocs,
+            ..Stats::ZERO
+        };
+        for block in tlsf.blocks() {
+            let size = block.usable_size() as usize;
+            if block.is_free() {
+                expected.free_bytes += size;
+                expected.free_blocks += 1;
+            } else {
+                expected.used_bytes += size;
+                expected.used_blocks += 1;
+            }
+        }
+        assert_eq!(expected, tlsf.stats());
+    }
+
+    #[test]
+    fn empty() {
+        let tlsf = Tlsf::<1>::empty();
+        assert_eq!(Stats::ZERO, tlsf.stats());
+    }
+
+    #[test]
+    fn it_works() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+        check(&tlsf);
+
+        let first = tlsf.malloc(4.try_into().unwrap()).unwrap();
+        let second = tlsf.memalign(Layout::new::<[u64; 2]>()).unwrap();
+        check(&tlsf);
+        assert_eq!(2, tlsf.stats().allocs);
+
+        assert!(tlsf.malloc(1024.try_into().unwrap()).is_none());
+        assert!(tlsf.malloc(120.try_into().unwrap()).is_none());
+        assert_eq!(2, tlsf.stats().failed_allocs);
+
+        let second = unsafe {
+            tlsf.realloc(NonNull::from(second).cast(), Layout::new::<[u8; 32]>(), 0)
+                .unwrap()
+        };
+        check(&tlsf);
+        let second = unsafe {
+            tlsf.realloc(NonNull::from(second).cast(), Layout::new::<u8>(), 0)
+                .unwrap()
+        };
+        check(&tlsf);
+        assert_eq!(2, tlsf.stats().allocs);
+
+        unsafe { tlsf.free(NonNull::from(first).cast()) }
+        unsafe { tlsf.free(NonNull::from(second).cast()) }
+        check(&tlsf);
+
+        let stats = tlsf.stats();
+        assert_eq!(2, stats.frees);
+        assert_eq!(0, stats.used_blocks);
+        assert_eq!(1, stats.free_blocks);
+    }
+
+    #[test]
+    fn zeroed() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        tlsf.malloc_zeroed(4.try_into().unwrap()).unwrap();
+        assert!(tlsf.memalign_zeroed(Layout::new::<[u8; 1024]>()).is_none());
+        check(&tlsf);
+
+        let stats = tlsf.stats();
+        assert_eq!(1, stats.allocs);
+        assert_eq!(1, stats.failed_allocs);
+    }
+
+    #[test]
+    fn uninitialized() {
+        let mut tlsf = Tlsf::<1>::empty();
+        assert!(tlsf.malloc(4.try_into().unwrap()).is_none());
+        assert_eq!(1, tlsf.stats().failed_allocs);
+    }
+
+    #[test]
+    fn watermarks() {
+        let mut tlsf = Tlsf::<2>::empty();
+        assert_eq!(Watermarks::ZERO, tlsf.watermarks());
+
+        // room for the two allocations below, with any block granularity, and for growing the
+        // second one in place
+        let mut memory = Aligned::<{ pool_len(&[16, 16, 80]) }>::new();
+        tlsf.initialize(&mut memory);
+        let initial = tlsf.stats().free_bytes;
+        assert_eq!(
+            Watermarks {
+                peak_used_bytes: 0,
+                min_free_bytes: initial,
+                peak_used_blocks: 0,
+            },
+            tlsf.watermarks()
+        );
+
+        let first = tlsf.malloc(8.try_into().unwrap()).unwrap();
+        let second = tlsf.malloc(4.try_into().unwrap()).unwrap();
+        let peak = tlsf.stats();
+        unsafe { tlsf.free(NonNull::from(first).cast()) }
+        assert_ne!(peak, tlsf.stats());
+
+        let marks = tlsf.watermarks();
+        assert_eq!(peak.used_bytes, marks.peak_used_bytes);
+        assert_eq!(peak.free_bytes, marks.min_free_bytes);
+        assert_eq!(2, marks.peak_used_blocks);
+
+        // growing in place also moves the watermarks
+        let second = unsafe {
+            tlsf.realloc(NonNull::from(second).cast(), Layout::new::<[u8; 64]>(), 0)
+                .unwrap()
+        };
+        assert_eq!(tlsf.stats().used_bytes, tlsf.watermarks().peak_used_bytes);
+        assert_eq!(tlsf.stats().free_bytes, tlsf.watermarks().min_free_bytes);
+
+        unsafe { tlsf.free(NonNull::from(second).cast()) }
+        tlsf.reset_watermarks();
+        assert_eq!(
+            Watermarks {
+                peak_used_bytes: 0,
+                min_free_bytes: initial,
+                peak_used_blocks: 0,
+            },
+            tlsf.watermarks()
+        );
+    }
+
+    #[test]
+    fn watermarks_try_grow_in_place() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = [MaybeUninit::uninit(); 32];
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf.malloc(4.try_into().unwrap()).unwrap();
+        let ptr = NonNull::from(alloc).cast();
+        unsafe { tlsf.try_grow_in_place(ptr, 64.try_into().unwrap()) }.unwrap();
+
+        let stats = tlsf.stats();
+        let marks = tlsf.watermarks();
+        assert_eq!(stats.used_bytes, marks.peak_used_bytes);
+        assert_eq!(stats.free_bytes, marks.min_free_bytes);
+    }
+
+    #[test]
+    fn watermarks_moving_realloc() {
+        let mut tlsf = Tlsf::<2>::empty();
+        let mut memory = Aligned::<{ 8 * consts::BLOCK_ALIGN as usize }>::new();
+        tlsf.initialize(&mut memory);
+
+        let size = Size::from(consts::BLOCK_ALIGN).try_into().unwrap();
+        let first = tlsf.malloc(size).unwrap();
+        // the usable size of the block includes the canary
+        let old_size = first.len() * 4 + consts::CANARY_SIZE as usize;
+        let ptr = NonNull::from(first).cast();
+        // keeps `first` from growing in place
+        tlsf.malloc(size).unwrap();
+
+        let layout = Layout::from_size_align(4 * usize::from(consts::BLOCK_ALIGN), 1).unwrap();
+        let alloc = unsafe { tlsf.realloc(ptr, layout, usize::MAX) }.unwrap();
+        assert_ne!(ptr, NonNull::from(alloc).cast());
+
+        // the old and the new block were both allocated during the move
+        let marks = tlsf.watermarks();
+        assert_eq!(3, marks.peak_used_blocks);
+        assert_eq!(tlsf.stats().used_bytes + old_size, marks.peak_used_bytes);
+    }
+}
@@ -1,26 +1,39 @@
 use crate::block::{Anchor, FreeBlock};
 use crate::header::Header;
 use crate::mapping::Indices;
+use crate::sl::{SecondLevel, Sl};
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     pub(super) unsafe fn unlink<'a>(&mut self, anchor: Anchor<'a>, block: &FreeBlock<'a>) {
         let Indices { fl, sl } = Self::mapping_insert(block.usable_size());
 
         #[cfg(any(fuzzing, test))]
         debug_assert!(self.get_free_list(fl, sl).is_some());
 
+        self.untrack_free(block.usable_size());
+
+        // `push` poisoned the block
+        #[cfg(feature = "hardening")]
+        if !block.is_poisoned(anchor) {
+            self.report(crate::Corruption::UseAfterFree {
+                block: anchor.offset_of(block).get(),
+            });
+        }
+
+        #[cfg(feature = "safe-linking")]
+        if !self.check_links(anchor, block, fl, sl) {
+            return;
+        }
+
         match (block.get_prev_free(), block.get_next_free()) {
             (None, None) => {
                 #[cfg(all(test, not(miri)))]
                 cov_mark::hit!(unlink_last);
 
-                self.set_free_list(fl, sl, None);
-
-                self.clear_sl_bit(fl, sl);
-
-                if self.is_sl_empty(fl) {
-                    self.clear_fl_bit(fl);
-                }
+                self.clear_free_list(fl, sl);
             }
 
             (None, Some(next_offset)) => {
@@ -53,27 +66,41 @@ impl<const FLL: usize> Header<FLL> {
             }
         }
     }
+
+    /// Empties free list (`fl`, `sl`) and clears its bitmap bits
+    ///
+    /// # Safety
+    /// - caller must perform bounds checks
+    pub(super) unsafe fn clear_free_list(&mut self, fl: u8, sl: u8) {
+        self.set_free_list(fl, sl, None);
+
+        self.clear_sl_bit(fl, sl);
+
+        if self.is_sl_empty(fl) {
+            self.clear_fl_bit(fl);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::mem::MaybeUninit;
-
     use super::*;
-    use crate::helpers::FreeBlocks;
+    use crate::consts::MIN_USABLE_SIZE as MIN;
+    use crate::helpers::{pool_len, Aligned, FreeBlocks};
 
     #[test]
     fn last_block_in_free_list() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 3];
+        let mut memory = Aligned::<{ pool_len(&[MIN]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let first = free_blocks.next(4, true);
+            let first = free_blocks.next(MIN, true);
             header.push(anchor, first.clone());
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
+            assert_eq!(MIN as usize, header.stats().free_bytes);
 
             {
                 #[cfg(not(miri))]
@@ -82,8 +109,9 @@ mod tests {
                 header.unlink(anchor, &first);
             }
 
-            assert!(!header.is_sl_bit_set(0, 1));
+            assert!(!header.is_list_set(MIN));
             assert!(!header.is_fl_bit_set(0));
+            assert_eq!(0, header.stats().free_bytes);
             assert!(header.linked_free_blocks(anchor).is_empty());
         }
     }
@@ -91,17 +119,17 @@ mod tests {
     #[test]
     fn free_list_head() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let tail = free_blocks.next(4, false);
+            let tail = free_blocks.next(MIN, false);
             header.push(anchor, tail.clone());
 
-            let head = free_blocks.next(4, true);
+            let head = free_blocks.next(MIN, true);
             header.push(anchor, head.clone());
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
 
             {
@@ -111,7 +139,7 @@ mod tests {
                 header.unlink(anchor, &head);
             }
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             let [remaining] = header.linked_free_blocks(anchor).try_into().unwrap();
             assert_eq!(anchor.offset_of(&tail), anchor.offset_of(&remaining));
@@ -121,17 +149,17 @@ mod tests {
     #[test]
     fn free_list_tail() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 5];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 2]) }>::new();
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let tail = free_blocks.next(4, false);
+            let tail = free_blocks.next(MIN, false);
             header.push(anchor, tail.clone());
 
-            let head = free_blocks.next(4, true);
+            let head = free_blocks.next(MIN, true);
             header.push(anchor, head.clone());
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
 
             {
@@ -141,7 +169,7 @@ mod tests {
                 header.unlink(anchor, &tail);
             }
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             let [remaining] = header.linked_free_blocks(anchor).try_into().unwrap();
             assert_eq!(anchor.offset_of(&head), anchor.offset_of(&remaining));
@@ -151,21 +179,21 @@ mod tests {
     #[test]
     fn from_middle_of_free_list() {
         let mut header = Header::<1>::new();
-        let mut memory = [MaybeUninit::uninit(); 7];
+        let mut memory = Aligned::<{ pool_len(&[MIN; 3]) }>::new();
 
         let mut free_blocks = FreeBlocks::new(&mut memory);
         let anchor = free_blocks.anchor;
         unsafe {
-            let tail = free_blocks.next(4, false);
+            let tail = free_blocks.next(MIN, false);
             header.push(anchor, tail.clone());
 
-            let middle = free_blocks.next(4, false);
+            let middle = free_blocks.next(MIN, false);
             header.push(anchor, middle.clone());
 
-            let head = free_blocks.next(4, true);
+            let head = free_blocks.next(MIN, true);
             header.push(anchor, head.clone());
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
 
             {
@@ -175,7 +203,7 @@ mod tests {
                 header.unlink(anchor, &middle);
             }
 
-            assert!(header.is_sl_bit_set(0, 1));
+            assert!(header.is_list_set(MIN));
             assert!(header.is_fl_bit_set(0));
             let [a, b] = header.linked_free_blocks(anchor).try_into().unwrap();
             assert_eq!(anchor.offset_of(&head), anchor.offset_of(&a));
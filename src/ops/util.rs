@@ -1,30 +1,37 @@
 use crate::block::{Anchor, FreeBlock, UsedBlock};
-use crate::consts;
 use crate::header::Header;
+use crate::sl::{SecondLevel, Sl};
+use crate::{consts, Size};
 
-pub fn round_up_block_size(num: u16) -> Option<u16> {
-    let multiple = consts::BLOCK_ALIGN as u16;
+pub fn round_up_block_size(num: Size) -> Option<Size> {
+    let num = num.checked_add(consts::CANARY_SIZE)?;
+    let num = num.max(consts::MIN_USABLE_SIZE);
+    let multiple = Size::from(consts::BLOCK_ALIGN);
     let rem = num % multiple;
     if rem == 0 {
         Some(num)
     } else {
-        u16::try_from((num as u32) + (multiple as u32 - rem as u32)).ok()
+        num.checked_add(multiple - rem)
     }
 }
 
-impl<const FLL: usize> Header<FLL> {
+impl<const FLL: usize, const SL: usize> Header<FLL, SL>
+where
+    Sl<SL>: SecondLevel,
+{
     pub(super) unsafe fn adjust_free_block_size<'a>(
         &mut self,
         anchor: Anchor<'a>,
         block: FreeBlock<'a>,
-        size: u16,
+        size: Size,
     ) -> FreeBlock<'a> {
-        if u32::from(block.usable_size()) >= u32::from(size) + u32::from(FreeBlock::HEADER_SIZE) {
+        if u64::from(block.usable_size()) >= u64::from(size) + u64::from(FreeBlock::HEADER_SIZE) {
             #[cfg(all(test, not(miri)))]
             cov_mark::hit!(alloc_adjust_size);
 
-            let at = usize::from(UsedBlock::HEADER_SIZE) + usize::from(size);
+            let at = usize::from(UsedBlock::HEADER_SIZE) + size as usize;
             let new = unsafe { anchor.split(&block, at) };
+            let new = unsafe { self.coalesce_next(anchor, new) };
             unsafe { self.push(anchor, new) }
         }
 
@@ -0,0 +1,314 @@
+use core::alloc::Layout;
+use core::mem::{self, MaybeUninit};
+use core::ptr::NonNull;
+
+use crate::block::{FreeBlock, UsedBlock};
+use crate::sl::{SecondLevel, Sl};
+use crate::{AllocError, NonZeroSize, Size, Tlsf};
+
+impl<'a, const FLL: usize, const POOLS: usize, const SL: usize> Tlsf<'a, FLL, POOLS, SL>
+where
+    Sl<SL>: SecondLevel,
+{
+    /// Gives the allocator a chunk of zeroed memory to manage
+    ///
+    /// This behaves like [`Tlsf::initialize`] but additionally lets the allocator keep track of the
+    /// memory that has never been handed out. [`Tlsf::malloc_zeroed`] and
+    /// [`Tlsf::memalign_zeroed`] skip zeroing that memory, unless the `hardening` feature is
+    /// enabled.
+    ///
+    /// # Safety
+    ///
+    /// - all of `memory` MUST be zeroed, e.g. it's a `static` variable placed in the `.bss` section
+    pub unsafe fn initialize_zeroed(&mut self, memory: &'a mut [MaybeUninit<u32>]) {
+        if self.pool_count != 0 {
+            return;
+        }
+
+        self.add_pool_zeroed(memory);
+    }
+
+    /// Gives the allocator an additional chunk of zeroed memory to manage
+    ///
+    /// This behaves like [`Tlsf::add_pool`]; see [`Tlsf::initialize_zeroed`] for details
+    ///
+    /// # Safety
+    ///
+    /// - all of `memory` MUST be zeroed
+    pub unsafe fn add_pool_zeroed(&mut self, memory: &'a mut [MaybeUninit<u32>]) -> bool {
+        let start = memory.as_ptr() as usize;
+        let index = self.pool_count;
+        if !self.add_pool(memory) {
+            return false;
+        }
+
+        // the `hardening` feature poisons free memory so it's never known to be zeroed
+        if cfg!(not(feature = "hardening")) {
+            self.zeroed_from[index] = start;
+        }
+
+        true
+    }
+
+    /// Allocates a zeroed memory block of the requested `size`
+    ///
+    /// See [`Tlsf::malloc`] for details
+    pub fn malloc_zeroed(&mut self, size: NonZeroSize) -> Option<&'a mut [u32]> {
+        let res = self
+            .anchor()
+            .ok_or(AllocError::Uninitialized)
+            .and_then(|anchor| unsafe { self.header.malloc(anchor, size) });
+        let alloc = self.count_alloc(res).ok()?;
+        Some(self.zero(alloc))
+    }
+
+    /// Allocates a zeroed memory block compatible with the specified `layout`
+    ///
+    /// See [`Tlsf::memalign`] for details
+    pub fn memalign_zeroed(&mut self, layout: Layout) -> Option<&'a mut [u32]> {
+        let res = self
+            .anchor()
+            .ok_or(AllocError::Uninitialized)
+            .and_then(|anchor| unsafe { self.header.memalign(anchor, layout) });
+        let alloc = self.count_alloc(res).ok()?;
+        Some(self.zero(alloc))
+    }
+
+    fn zero(&mut self, alloc: &'a mut [MaybeUninit<u32>]) -> &'a mut [u32] {
+        let start = alloc.as_ptr() as usize;
+        let end = alloc.as_ptr_range().end as usize;
+
+        // the free list links of a free block overlap with the start of its body so that part of
+        // the body is never known to be zeroed
+        let links_size = FreeBlock::HEADER_SIZE - UsedBlock::HEADER_SIZE;
+        let links_end = start.wrapping_add(links_size.into());
+        let zeroed_from = self
+            .pool_index(start)
+            .map(|index| self.zeroed_from[index])
+            .unwrap_or(usize::MAX);
+        let dirty_end = zeroed_from.max(links_end).min(end);
+        let dirty_len = dirty_end.wrapping_sub(start) / mem::size_of::<u32>();
+
+        #[cfg(all(test, not(miri), not(feature = "hardening")))]
+        if dirty_len < alloc.len() {
+            cov_mark::hit!(zeroed_skip_pristine_memory);
+        }
+
+        alloc.iter_mut().take(dirty_len).for_each(|mu| {
+            mu.write(0);
+        });
+
+        self.mark_dirty(start, end);
+
+        // SAFETY: the first `dirty_len` words were just zeroed and the rest were already zeroed
+        unsafe { &mut *(alloc as *mut [MaybeUninit<u32>] as *mut [u32]) }
+    }
+
+    /// Records that the memory handed out in `alloc` may no longer be zeroed
+    pub(super) fn mark_alloc_dirty(&mut self, alloc: &[MaybeUninit<u32>]) {
+        let range = alloc.as_ptr_range();
+        self.mark_dirty(range.start as usize, range.end as usize);
+    }
+
+    /// Records that the memory of the used block behind `ptr` may no longer be zeroed
+    ///
+    /// Freeing a block may merge it with the next physical block which leaves the header of the
+    /// latter in the body of the resulting free block
+    pub(super) fn mark_freed_dirty(&mut self, ptr: NonNull<u32>, usable_size: Size) {
+        let start = ptr.as_ptr() as usize;
+        let end = start
+            .wrapping_add(usable_size as usize)
+            .wrapping_add(FreeBlock::HEADER_SIZE.into());
+        self.mark_dirty(start, end);
+    }
+
+    /// Records that the memory of the pool that contains `start` may no longer be zeroed up to
+    /// `end`
+    fn mark_dirty(&mut self, start: usize, end: usize) {
+        let Some(index) = self.pool_index(start) else {
+            return;
+        };
+
+        let zeroed_from = &mut self.zeroed_from[index];
+        if end > *zeroed_from {
+            *zeroed_from = end;
+        }
+    }
+
+    fn pool_index(&self, addr: usize) -> Option<usize> {
+        self.pools
+            .get(..self.pool_count)?
+            .iter()
+            .position(|pool| pool.contains(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{self, CANARY_SIZE as CANARY};
+    use crate::helpers::{added_pool_len, pool_len, Aligned, MIN_ALLOC};
+
+    const GRANULARITY: Size = consts::BLOCK_ALIGN as Size;
+    // words in `GRANULARITY` bytes
+    const WORDS: usize = GRANULARITY as usize / 4;
+    // words taken by the canary at the end of each block
+    const CANARY_WORDS: usize = CANARY as usize / 4;
+
+    #[test]
+    fn malloc_zeroed() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[5 * GRANULARITY]) }>::filled(!0);
+        tlsf.initialize(&mut memory);
+
+        let alloc = tlsf
+            .malloc_zeroed((5 * GRANULARITY - CANARY).try_into().unwrap())
+            .unwrap();
+        assert_eq!([0; 5 * WORDS - CANARY_WORDS], alloc);
+    }
+
+    #[test]
+    fn memalign_zeroed() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[2 * GRANULARITY; 2]) }>::filled(!0);
+        tlsf.initialize(&mut memory);
+
+        let layout = Layout::from_size_align((GRANULARITY + 1 - CANARY) as usize, 1).unwrap();
+        let alloc = tlsf.memalign_zeroed(layout).unwrap();
+        assert_eq!([0; 2 * WORDS - CANARY_WORDS], alloc);
+    }
+
+    // the `hardening` feature never skips zeroing
+    #[cfg(not(feature = "hardening"))]
+    #[test]
+    fn pristine_memory() {
+        // large enough to extend past the free list links
+        const SIZE: Size = 2 * consts::MIN_USABLE_SIZE;
+        const ZEROED: [u32; SIZE as usize / 4] = [0; SIZE as usize / 4];
+
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[SIZE, SIZE, 8 * GRANULARITY]) }>::filled(0);
+        unsafe { tlsf.initialize_zeroed(&mut memory) }
+
+        let size = SIZE.try_into().unwrap();
+        let first = {
+            #[cfg(not(miri))]
+            cov_mark::check!(zeroed_skip_pristine_memory);
+
+            tlsf.malloc_zeroed(size)
+        }
+        .unwrap();
+        assert_eq!(ZEROED, first);
+        first.fill(!0);
+
+        let second = tlsf.malloc_zeroed(size).unwrap();
+        assert_eq!(ZEROED, second);
+        second.fill(!0);
+
+        // the block is merged with the rest of the free memory
+        unsafe { tlsf.free(NonNull::from(second).cast()) }
+
+        // the reused memory is zeroed again
+        let third = tlsf.malloc_zeroed(size).unwrap();
+        assert_eq!(ZEROED, third);
+        third.fill(!0);
+
+        let rest = tlsf
+            .malloc_zeroed((7 * GRANULARITY).try_into().unwrap())
+            .unwrap();
+        assert_eq!([0; 8 * WORDS], rest);
+    }
+
+    #[test]
+    fn pristine_memory_after_realloc() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[6 * GRANULARITY, 7 * GRANULARITY]) }>::filled(0);
+        unsafe { tlsf.initialize_zeroed(&mut memory) }
+
+        let first = tlsf
+            .malloc_zeroed((6 * GRANULARITY).try_into().unwrap())
+            .unwrap();
+        first.fill(!0);
+        let ptr = NonNull::from(first).cast();
+
+        let size = (MIN_ALLOC - CANARY).try_into().unwrap();
+        let first = unsafe { tlsf.shrink_in_place(ptr, size) }.unwrap();
+        assert_eq!(size.get() as usize / 4, first.len());
+
+        // the block split off `first`, merged with the rest of the pool, holds dirty memory
+        let [free] = tlsf.free_blocks().try_into().unwrap();
+        let size = free.usable_size() - CANARY;
+        let second = tlsf.malloc_zeroed(size.try_into().unwrap()).unwrap();
+        assert_eq!(size as usize / 4, second.len());
+        assert!(second.iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    fn pristine_memory_after_failed_realloc() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ 16 * WORDS }>::filled(0);
+        unsafe { tlsf.initialize_zeroed(&mut memory) }
+
+        let alloc = tlsf.malloc_zeroed(GRANULARITY.try_into().unwrap()).unwrap();
+        let ptr = NonNull::from(alloc).cast();
+        let zeroed_from = tlsf.zeroed_from[0];
+
+        let empty = Layout::from_size_align(0, 1).unwrap();
+        assert!(unsafe { tlsf.realloc(ptr, empty, usize::MAX) }.is_none());
+        let large = Layout::new::<[u32; 64]>();
+        assert!(unsafe { tlsf.realloc(ptr, large, usize::MAX) }.is_none());
+        assert_eq!(zeroed_from, tlsf.zeroed_from[0]);
+    }
+
+    #[test]
+    fn pristine_memory_across_blocks() {
+        // too large for a single block
+        let mut tlsf = Tlsf::<{ consts::MAX_FLL }>::empty();
+        let mut memory = vec![MaybeUninit::new(0); (65_540 + 20_000) / 4];
+        unsafe { tlsf.initialize_zeroed(&mut memory) }
+
+        let first = tlsf.malloc_zeroed(50_000.try_into().unwrap()).unwrap();
+        assert!(first.iter().all(|word| *word == 0));
+        first.fill(!0);
+
+        // the rest of the first block, which is next to the second block
+        let second = tlsf.malloc_zeroed(15_000.try_into().unwrap()).unwrap();
+        assert!(second.iter().all(|word| *word == 0));
+        second.fill(!0);
+
+        let third = tlsf.malloc_zeroed(19_000.try_into().unwrap()).unwrap();
+        assert!(third.iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    fn pristine_pools() {
+        let mut tlsf = Tlsf::<1, 2>::empty();
+        let mut dirty = Aligned::<{ pool_len(&[3 * GRANULARITY]) }>::filled(!0);
+        let mut zeroed = Aligned::<{ added_pool_len(&[3 * GRANULARITY]) }>::filled(0);
+        assert!(tlsf.add_pool(&mut dirty));
+        assert!(unsafe { tlsf.add_pool_zeroed(&mut zeroed) });
+
+        let size = (3 * GRANULARITY - CANARY).try_into().unwrap();
+        let first = tlsf.malloc_zeroed(size).unwrap();
+        assert_eq!([0; 3 * WORDS - CANARY_WORDS], first);
+        first.fill(!0);
+
+        let second = tlsf.malloc_zeroed(size).unwrap();
+        assert_eq!([0; 3 * WORDS - CANARY_WORDS], second);
+    }
+
+    #[test]
+    fn initialized_twice() {
+        let mut tlsf = Tlsf::<1>::empty();
+        let mut memory = Aligned::<{ pool_len(&[6 * GRANULARITY]) }>::filled(!0);
+        tlsf.initialize(&mut memory);
+
+        let mut zeroed = Aligned::<{ pool_len(&[6 * GRANULARITY]) }>::filled(0);
+        unsafe { tlsf.initialize_zeroed(&mut zeroed) }
+
+        let alloc = tlsf
+            .malloc_zeroed((6 * GRANULARITY - CANARY).try_into().unwrap())
+            .unwrap();
+        assert_eq!([0; 6 * WORDS - CANARY_WORDS], alloc);
+    }
+}
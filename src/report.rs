@@ -0,0 +1,26 @@
+#[allow(unused_imports)] // used by API docs
+use crate::Tlsf;
+
+/// What the allocator did with the memory given to [`Tlsf::try_initialize`] or
+/// [`Tlsf::try_add_pool`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolReport {
+    /// Size, in bytes, of the memory blocks created in the pool, including their headers
+    pub managed_bytes: usize,
+
+    /// Size, in bytes, of the given memory that the allocator will never use
+    ///
+    /// This includes the bytes needed to align the start of the pool, the first `BLOCK_ALIGN` bytes
+    /// of the first pool and any tail too small to hold a block
+    pub discarded_bytes: usize,
+
+    /// Size, in bytes, of the memory, included in `discarded_bytes`, that did not fit in the offset
+    /// space: 256 KiB, shared by all pools, unless the `wide-offsets` feature is enabled
+    pub truncated_bytes: usize,
+
+    /// Number of free blocks the pool was split into
+    ///
+    /// This is more than one when the pool is larger than the largest possible block: about 64
+    /// KiB unless the `wide-sizes` feature is enabled
+    pub blocks: usize,
+}
@@ -0,0 +1,49 @@
+/// Selects the number of second level free lists, `N`, per first level of a [`Tlsf`] allocator
+///
+/// Supported values are 4, 8, 16 and 32. Fewer lists shrink the allocator's header; more lists
+/// reduce internal fragmentation.
+///
+/// [`Tlsf`]: crate::Tlsf
+pub struct Sl<const N: usize>;
+
+/// Implemented by the supported [`Sl`] configurations
+///
+/// This trait is sealed and cannot be implemented outside this crate
+pub trait SecondLevel: sealed::Sealed {}
+
+pub(crate) mod sealed {
+    pub trait Sealed {
+        /// The bitmap that tracks which second level free lists are non-empty
+        type Bitmap: Copy + Into<u32>;
+
+        const EMPTY: Self::Bitmap;
+
+        /// Truncates `bits` to the width of the bitmap
+        fn from_bits(bits: u32) -> Self::Bitmap;
+    }
+}
+
+macro_rules! second_level {
+    ($($n:literal => $bitmap:ty,)+) => {
+        $(
+            impl SecondLevel for Sl<$n> {}
+
+            impl sealed::Sealed for Sl<$n> {
+                type Bitmap = $bitmap;
+
+                const EMPTY: $bitmap = 0;
+
+                fn from_bits(bits: u32) -> $bitmap {
+                    bits as $bitmap
+                }
+            }
+        )+
+    };
+}
+
+second_level! {
+    4 => u8,
+    8 => u8,
+    16 => u16,
+    32 => u32,
+}
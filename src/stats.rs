@@ -0,0 +1,118 @@
+use crate::Size;
+#[allow(unused_imports)] // used by API docs
+use crate::Tlsf;
+
+/// Usage statistics of a [`Tlsf`] allocator, see [`Tlsf::stats`]
+///
+/// The allocator keeps these counters up to date as it operates so reading them is a constant
+/// time (`O(1)`) operation. The request counters, `allocs`, `frees` and `failed_allocs`, wrap
+/// around after `u32::MAX` requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    /// Total usable size, in bytes, of the free blocks
+    pub free_bytes: usize,
+
+    /// Total usable size, in bytes, of the used blocks
+    pub used_bytes: usize,
+
+    /// Number of free blocks
+    pub free_blocks: usize,
+
+    /// Number of used blocks, i.e. live allocations
+    pub used_blocks: usize,
+
+    /// Number of successful allocation requests
+    ///
+    /// Moving a block in [`Tlsf::realloc`] is not counted as an allocation
+    pub allocs: usize,
+
+    /// Number of [`Tlsf::free`] calls
+    pub frees: usize,
+
+    /// Number of failed allocation requests
+    pub failed_allocs: usize,
+}
+
+impl Stats {
+    #[cfg(test)]
+    pub(crate) const ZERO: Self = Self {
+        free_bytes: 0,
+        used_bytes: 0,
+        free_blocks: 0,
+        used_blocks: 0,
+        allocs: 0,
+        frees: 0,
+        failed_allocs: 0,
+    };
+}
+
+/// High-water marks of the memory usage of a [`Tlsf`] allocator, see [`Tlsf::watermarks`]
+///
+/// The marks cover the period since the allocator was initialized or since the last call to
+/// [`Tlsf::reset_watermarks`], whichever happened last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watermarks {
+    /// Largest value of [`Stats::used_bytes`]
+    pub peak_used_bytes: usize,
+
+    /// Smallest value of [`Stats::free_bytes`]
+    pub min_free_bytes: usize,
+
+    /// Largest value of [`Stats::used_blocks`], i.e. the peak number of live allocations
+    pub peak_used_blocks: usize,
+}
+
+impl Watermarks {
+    pub(crate) const ZERO: Self = Self {
+        peak_used_bytes: 0,
+        min_free_bytes: 0,
+        peak_used_blocks: 0,
+    };
+}
+
+/// Fragmentation metrics of a [`Tlsf`] allocator, see [`Tlsf::fragmentation`]
+///
+/// `histogram` is indexed by the `(fl, sl)` size classes of the allocator and `largest_spans` holds
+/// up to `N` of the largest free blocks, largest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fragmentation<const FLL: usize, const SL: usize, const N: usize> {
+    /// Total usable size, in bytes, of the free blocks
+    pub free_bytes: usize,
+
+    /// Number of free blocks
+    pub free_blocks: usize,
+
+    /// Usable size, in bytes, of the largest free block
+    pub largest_free_block: Size,
+
+    /// Number of free blocks in each size class, indexed by `[fl][sl]`
+    pub histogram: [[usize; SL]; FLL],
+
+    /// The largest free blocks, sorted by decreasing size; `None` when there are fewer than `N`
+    /// free blocks
+    pub largest_spans: [Option<FreeSpan>; N],
+}
+
+impl<const FLL: usize, const SL: usize, const N: usize> Fragmentation<FLL, SL, N> {
+    /// Returns the external fragmentation ratio, `1 - largest_free_block / free_bytes`
+    ///
+    /// The ratio is `0` when all the free memory is in a single block, or there's no free memory,
+    /// and approaches `1` as the free memory gets split into many small blocks
+    pub fn external_ratio(&self) -> f32 {
+        if self.free_bytes == 0 {
+            return 0.;
+        }
+
+        1. - self.largest_free_block as f32 / self.free_bytes as f32
+    }
+}
+
+/// A free block of a [`Tlsf`] allocator, see [`Fragmentation::largest_spans`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FreeSpan {
+    /// Compressed offset of the block, see [`Block::offset`](crate::Block::offset)
+    pub offset: usize,
+
+    /// Usable size of the block in bytes
+    pub size: Size,
+}
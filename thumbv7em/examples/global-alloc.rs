@@ -26,93 +26,45 @@ fn main() -> ! {
     drop(xs);
 
     info!("boxed {}", *Box::new(Zst));
+    heap::log_stats();
 
     thumbv7m::exit()
 }
 
 mod heap {
-    use core::alloc::{GlobalAlloc, Layout};
     use core::mem::MaybeUninit;
-    use core::ptr::{self, NonNull};
 
     use defmt::*;
     use spin::mutex::SpinMutex;
-    use tlsf::Tlsf;
+    use tlsf::global::{GlobalTlsf, Lock, ReallocPolicy};
 
     #[global_allocator]
-    static HEAP: Heap = Heap {
-        inner: SpinMutex::new(Tlsf::empty()),
-    };
+    static HEAP: GlobalTlsf<SpinLock, 2> =
+        GlobalTlsf::new(SpinLock(SpinMutex::new(())), ReallocPolicy::InPlace);
 
-    struct Heap {
-        inner: SpinMutex<Tlsf<'static, 2>>,
-    }
-
-    pub fn initialize(memory: &'static mut [MaybeUninit<u32>]) {
-        HEAP.inner.lock().initialize(memory)
-    }
-
-    unsafe impl GlobalAlloc for Heap {
-        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-            let mut tlsf = self.inner.lock();
+    struct SpinLock(SpinMutex<()>);
 
-            log_stats(&tlsf);
-
-            let ptr = tlsf
-                .memalign(layout)
-                .map(|nn| nn.as_mut_ptr().cast())
-                .unwrap_or(ptr::null_mut());
-
-            debug!(
-                "alloc(Layout {{ size: {}, align: {} }} -> {})",
-                layout.size(),
-                layout.align(),
-                ptr
-            );
-
-            log_stats(&tlsf);
-
-            ptr
-        }
-
-        unsafe fn dealloc(&self, ptr: *mut u8, _: Layout) {
-            if let Some(nn) = NonNull::new(ptr) {
-                let mut tlsf = self.inner.lock();
-
-                log_stats(&tlsf);
-
-                debug!("free({})", nn);
-
-                tlsf.free(nn.cast());
-
-                log_stats(&tlsf);
-            }
+    // SAFETY: the spin mutex serializes all the `with_lock` calls
+    unsafe impl Lock for SpinLock {
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let _guard = self.0.lock();
+            f()
         }
     }
 
-    fn log_stats(tlsf: &Tlsf<2>) {
-        let mut total_used = 0;
-        let mut used_count = 0;
-        let mut total_free = 0;
-        let mut free_count = 0;
-        for block in tlsf.blocks() {
-            if block.is_free() {
-                free_count += 1;
-                total_free += block.usable_size();
-            } else {
-                used_count += 1;
-                total_used += block.usable_size();
-            }
-        }
+    pub fn initialize(memory: &'static mut [MaybeUninit<u32>]) {
+        HEAP.initialize(memory);
+        log_stats();
+    }
 
+    pub fn log_stats() {
+        let stats = HEAP.with(|tlsf| tlsf.stats());
         trace!(
             "{}B of used memory across {} blocks; {}B of free memory across {} blocks",
-            total_used,
-            used_count,
-            total_free,
-            free_count
+            stats.used_bytes,
+            stats.used_blocks,
+            stats.free_bytes,
+            stats.free_blocks
         );
     }
-
-    unsafe impl Sync for Heap {}
 }
@@ -6,11 +6,28 @@ use core::panic::PanicInfo;
 use tlsf::Tlsf;
 
 #[no_mangle]
-fn _start() -> [usize; 3] {
+fn _start() -> [usize; 20] {
     [
+        Tlsf::<2>::add_pool as usize,
+        Tlsf::<2>::can_allocate as usize,
+        Tlsf::<2>::check as usize,
+        Tlsf::<2>::checked_blocks as usize,
+        Tlsf::<2>::fragmentation::<4> as usize,
         Tlsf::<2>::free as usize,
         Tlsf::<2>::initialize as usize,
+        Tlsf::<2>::largest_free_class as usize,
+        Tlsf::<2>::malloc_zeroed as usize,
         Tlsf::<2>::memalign as usize,
+        Tlsf::<2>::memalign_zeroed as usize,
+        Tlsf::<2>::realloc as usize,
+        Tlsf::<2>::shrink_in_place as usize,
+        Tlsf::<2>::stats as usize,
+        Tlsf::<2>::try_add_pool as usize,
+        Tlsf::<2>::try_free as usize,
+        Tlsf::<2>::try_grow_in_place as usize,
+        Tlsf::<2>::try_initialize as usize,
+        Tlsf::<2>::try_malloc as usize,
+        Tlsf::<2>::try_memalign as usize,
     ]
 }
 
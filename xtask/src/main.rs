@@ -38,6 +38,10 @@ fn run_ci(project_root: &Path) -> Result<()> {
             .args(["miri", "test"])
             .current_dir(project_root))?;
 
+        run(Command::new("cargo")
+            .args(["test", "--features", "nightly,allocator-api2"])
+            .current_dir(project_root))?;
+
         run(Command::new("cargo")
             .args(["fuzz", "build"])
             .current_dir(project_root))?;
@@ -54,12 +58,36 @@ fn run_ci(project_root: &Path) -> Result<()> {
                 .current_dir(project_root))?;
         }
     } else {
-        run(Command::new("cargo").arg("test").current_dir(project_root))?;
+        run(Command::new("cargo")
+            .args(["test", "--features", "global,allocator-api2"])
+            .current_dir(project_root))?;
+
+        for features in [
+            "wide-offsets",
+            "wide-sizes",
+            "align-8",
+            "align-16",
+            "hardening",
+            "safe-linking",
+            "header-checksum",
+            "align-16,header-checksum",
+            "wide-offsets,wide-sizes,align-8,header-checksum",
+        ] {
+            run(Command::new("cargo")
+                .args(["test", "--features", features])
+                .current_dir(project_root))?;
+        }
 
         run(Command::new("cargo")
             .args(["build", "--bin", "no-panics"])
             .current_dir(project_root.join("thumbv7em")))?;
 
+        for features in ["hardening", "header-checksum"] {
+            run(Command::new("cargo")
+                .args(["build", "--bin", "no-panics", "--features", features])
+                .current_dir(project_root.join("thumbv7em")))?;
+        }
+
         let subdirs = [".", "thumbv7em"];
 
         for subdir in subdirs {
@@ -67,6 +95,17 @@ fn run_ci(project_root: &Path) -> Result<()> {
                 .args(["clippy", "--all-targets", "--", "--deny", "warnings"])
                 .current_dir(project_root.join(subdir)))?;
         }
+
+        // the non-default layouts combined
+        run(Command::new("cargo")
+            .args([
+                "clippy",
+                "--all-targets",
+                "--features",
+                "wide-offsets,wide-sizes,align-8,header-checksum,global,allocator-api2",
+            ])
+            .args(["--", "--deny", "warnings"])
+            .current_dir(project_root))?;
     }
 
     Ok(())
@@ -106,6 +145,7 @@ fn install_pre_commit_hook(project_root: &Path, subcommand: &str) -> Result<()>
 
     let mut writer = OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .mode(0o755)
         .open(pre_commit_file)?;